@@ -1,4 +1,8 @@
+use crate::debruijn::DExp;
+
+use std::collections::hash_map::DefaultHasher;
 use std::fmt;
+use std::hash::{ Hash, Hasher };
 
 #[derive(PartialEq, Clone)]
 pub enum Exp {
@@ -6,6 +10,40 @@ pub enum Exp {
     Call(Box<Exp>, Box<Exp>),
     Lamb(String, Box<Exp>),
 }
+impl Exp {
+    /// Counts the nodes in an expression.
+    pub fn size(&self) -> usize {
+        match self {
+            Exp::Var(_) => 1,
+            Exp::Call(a, b) => 1 + a.size() + b.size(),
+            Exp::Lamb(_, r) => 1 + r.size(),
+        }
+    }
+    /// Checks whether two expressions are the same up to renaming of bound variables.
+    pub fn alpha_eq(&self, other: &Exp) -> bool {
+        DExp::from_exp(self) == DExp::from_exp(other)
+    }
+    /// A hash that is the same for alpha equivalent expressions.
+    pub fn alpha_hash(&self) -> u64 {
+        let mut h = DefaultHasher::new();
+        DExp::from_exp(self).hash(&mut h);
+        h.finish()
+    }
+}
+
+/// Like `assert_eq!`, but compares expressions up to alpha equivalence.
+#[cfg(test)]
+macro_rules! assert_alpha_eq {
+    ($left:expr, $right:expr) => {
+        match (&$left, &$right) {
+            (left, right) => if !left.alpha_eq(right) {
+                panic!("assertion failed: `left.alpha_eq(right)`\n  left: `{}`,\n right: `{}`", left, right)
+            }
+        }
+    };
+}
+#[cfg(test)]
+pub(crate) use assert_alpha_eq;
 impl fmt::Display for Exp {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -44,6 +82,7 @@ impl fmt::Debug for Exp {
 #[cfg(test)]
 mod tests {
     use super::Exp::*;
+    use crate::parser::{ parse, ParseError };
     #[test]
     fn display_var() {
         assert_eq!(format!("{}", Var("x".to_string())), "x");
@@ -57,4 +96,29 @@ mod tests {
         )), "x y");
         assert_eq!(format!("{}", Var("α".to_string())), "α");
     }
-}
\ No newline at end of file
+    #[test]
+    fn size() {
+        assert_eq!(Var("x".to_string()).size(), 1);
+        assert_eq!(Call(
+            Box::new(Lamb("x".to_string(), Box::new(Var("x".to_string())))),
+            Box::new(Var("y".to_string())),
+        ).size(), 4);
+    }
+    #[test]
+    fn alpha_equivalence() -> Result<(), ParseError> {
+        assert!(parse("\\x. x")?.alpha_eq(&parse("\\y. y")?));
+        assert!(parse("\\x y. x y z")?.alpha_eq(&parse("\\a b. a b z")?));
+        assert!(!parse("\\x y. x")?.alpha_eq(&parse("\\x y. y")?));
+        assert!(!parse("\\x. y")?.alpha_eq(&parse("\\y. y")?));
+        assert!(!parse("x")?.alpha_eq(&parse("y")?));
+        assert_alpha_eq!(parse("(\\x. x) (\\f x. f x)")?, parse("(\\a. a) (\\g y. g y)")?);
+        Ok(())
+    }
+    #[test]
+    fn alpha_hashes() -> Result<(), ParseError> {
+        assert_eq!(parse("\\x y. x")?.alpha_hash(), parse("\\a b. a")?.alpha_hash());
+        assert_eq!(parse("\\x. x z")?.alpha_hash(), parse("\\w. w z")?.alpha_hash());
+        assert_ne!(parse("\\x y. x")?.alpha_hash(), parse("\\x y. y")?.alpha_hash());
+        Ok(())
+    }
+}
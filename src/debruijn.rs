@@ -0,0 +1,326 @@
+use crate::code::Exp;
+use crate::reduce::{ Reduc, Term, View, NameStyle, fresh };
+
+use std::fmt;
+use std::hash::{ Hash, Hasher };
+
+/// A nameless term, where bound variables are de Bruijn indices counting the
+/// lambdas between a variable and its binder. Binders keep their original
+/// name so terms can be displayed with the names they were written with.
+#[derive(Clone)]
+pub enum DExp {
+    Var(usize),
+    Free(String),
+    Call(Box<DExp>, Box<DExp>),
+    Lamb(String, Box<DExp>),
+}
+use DExp::*;
+
+/// Terms compare equal regardless of binder names, i.e. up to alpha equivalence.
+impl PartialEq for DExp {
+    fn eq(&self, other: &DExp) -> bool {
+        match (self, other) {
+            (Var(i), Var(j)) => i == j,
+            (Free(x), Free(y)) => x == y,
+            (Call(a, b), Call(c, d)) => a == c && b == d,
+            (Lamb(_, r), Lamb(_, s)) => r == s,
+            _ => false
+        }
+    }
+}
+impl Eq for DExp {}
+impl Hash for DExp {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Var(i) => (0u8, i).hash(state),
+            Free(n) => (1u8, n).hash(state),
+            Call(a, b) => {
+                2u8.hash(state);
+                a.hash(state);
+                b.hash(state);
+            }
+            Lamb(_, r) => {
+                3u8.hash(state);
+                r.hash(state);
+            }
+        }
+    }
+}
+
+impl DExp {
+    pub fn from_exp(ex: &Exp) -> DExp {
+        from_exp(ex, &mut Vec::new())
+    }
+    /// Converts back to a named term, renaming binders in the style `names`
+    /// where their original name would capture or be captured.
+    pub fn to_exp(&self, names: NameStyle) -> Exp {
+        to_exp(self, names, &mut Vec::new())
+    }
+}
+
+fn from_exp<'a>(ex: &'a Exp, ctx: &mut Vec<&'a str>) -> DExp {
+    match ex {
+        Exp::Var(n) => match ctx.iter().rev().position(|x| x == n) {
+            Some(i) => Var(i),
+            None => Free(n.clone())
+        }
+        Exp::Call(a, b) => Call(Box::new(from_exp(a, ctx)), Box::new(from_exp(b, ctx))),
+        Exp::Lamb(x, r) => {
+            ctx.push(x);
+            let r = from_exp(r, ctx);
+            ctx.pop();
+            Lamb(x.clone(), Box::new(r))
+        }
+    }
+}
+
+fn to_exp(ex: &DExp, names: NameStyle, ctx: &mut Vec<String>) -> Exp {
+    match ex {
+        Var(i) => Exp::Var(ctx[ctx.len() - 1 - i].clone()),
+        Free(n) => Exp::Var(n.clone()),
+        Call(a, b) => Exp::Call(Box::new(to_exp(a, names, ctx)), Box::new(to_exp(b, names, ctx))),
+        Lamb(x, r) => {
+            let x = if clashes(x, r, 1, ctx) {
+                fresh(x, names, |n| clashes(n, r, 1, ctx))
+            } else {
+                x.clone()
+            };
+            ctx.push(x);
+            let r = to_exp(r, names, ctx);
+            Exp::Lamb(ctx.pop().unwrap(), Box::new(r))
+        }
+    }
+}
+
+/// Checks whether naming a binder `x` would make a variable in its body
+/// refer to the wrong thing: either a free variable called `x`, or a
+/// reference to an enclosing binder already named `x`.
+fn clashes(x: &str, ex: &DExp, depth: usize, ctx: &[String]) -> bool {
+    match ex {
+        Var(i) => *i >= depth && ctx[ctx.len() - 1 - (i - depth)] == x,
+        Free(n) => n == x,
+        Call(a, b) => clashes(x, a, depth, ctx) || clashes(x, b, depth, ctx),
+        Lamb(_, r) => clashes(x, r, depth + 1, ctx)
+    }
+}
+
+/// Adds `d` to every variable in `ex` that refers past the `cutoff` innermost binders.
+pub fn shift(ex: DExp, d: isize, cutoff: usize) -> DExp {
+    match ex {
+        Var(i) => if i >= cutoff {
+            Var((i as isize + d) as usize)
+        } else {
+            Var(i)
+        }
+        Free(n) => Free(n),
+        Call(a, b) => Call(Box::new(shift(*a, d, cutoff)), Box::new(shift(*b, d, cutoff))),
+        Lamb(x, r) => Lamb(x, Box::new(shift(*r, d, cutoff + 1)))
+    }
+}
+
+/// Replaces variable `j` in `ex` with `new`.
+pub fn sub(ex: DExp, j: usize, new: &DExp) -> DExp {
+    sub_under(ex, j, new, 0)
+}
+
+/// Substitutes `arg` for the variable bound by the lambda whose body is
+/// `body`, which no longer has that binder around it afterwards.
+fn beta(body: DExp, arg: &DExp) -> DExp {
+    shift(sub(body, 0, &shift(arg.clone(), 1, 0)), -1, 0)
+}
+
+/// Substitution below `depth` binders, shifting `new` only where it is inserted.
+fn sub_under(ex: DExp, j: usize, new: &DExp, depth: usize) -> DExp {
+    match ex {
+        Var(i) => if i == j + depth {
+            shift(new.clone(), depth as isize, 0)
+        } else {
+            Var(i)
+        }
+        Free(n) => Free(n),
+        Call(a, b) => Call(
+            Box::new(sub_under(*a, j, new, depth)),
+            Box::new(sub_under(*b, j, new, depth))
+        ),
+        Lamb(x, r) => Lamb(x, Box::new(sub_under(*r, j, new, depth + 1)))
+    }
+}
+
+fn occurs(j: usize, ex: &DExp) -> bool {
+    match ex {
+        Var(i) => *i == j,
+        Free(_) => false,
+        Call(a, b) => occurs(j, a) || occurs(j, b),
+        Lamb(_, r) => occurs(j + 1, r)
+    }
+}
+
+fn reduce_with(ex: DExp, red: &Reduc) -> DExp {
+    match (ex, red) {
+        (Call(a, b), Reduc::Beta) => match *a {
+            Lamb(_, r) => beta(*r, &b),
+            a => panic!("bad beta reduction: lhs {}", a)
+        }
+        (Call(a, b), red) => match red {
+            Reduc::Left(red) => Call(Box::new(reduce_with(*a, red)), b),
+            Reduc::Right(red) => Call(a, Box::new(reduce_with(*b, red))),
+            Reduc::Irred => Call(a, b),
+            red => panic!("bad reduction: {} on {}", red, Call(a, b))
+        }
+        (Lamb(x, r), Reduc::Eta) => match *r {
+            Call(a, b) if *b == Var(0) && !occurs(0, &a) => shift(*a, -1, 0),
+            r => panic!("bad eta reduction: {}", Lamb(x, Box::new(r)))
+        }
+        (Lamb(x, r), red) => match red {
+            Reduc::Body(red) => Lamb(x, Box::new(reduce_with(*r, red))),
+            Reduc::Irred => Lamb(x, r),
+            red => panic!("bad reduction: {} on {}", red, Lamb(x, r))
+        }
+        (ex, Reduc::Irred) => ex,
+        (ex, red) => panic!("bad reduction: {} on {}", red, ex)
+    }
+}
+
+impl Term for DExp {
+    /// Indices never need renaming.
+    type Names = ();
+    fn view(&self) -> View<'_, DExp> {
+        match self {
+            Var(_) | Free(_) => View::Var,
+            Call(a, b) => View::Call(a, b),
+            Lamb(_, r) => View::Lamb(r),
+        }
+    }
+    fn is_eta(&self) -> bool {
+        match self {
+            Lamb(_, r) => match **r {
+                Call(ref a, ref b) => **b == Var(0) && !occurs(0, a),
+                _ => false
+            }
+            _ => false
+        }
+    }
+    fn reduce_with(self, red: &Reduc, _: ()) -> DExp {
+        reduce_with(self, red)
+    }
+    fn size(&self) -> usize {
+        match self {
+            Var(_) | Free(_) => 1,
+            Call(a, b) => 1 + a.size() + b.size(),
+            Lamb(_, r) => 1 + r.size(),
+        }
+    }
+}
+
+impl fmt::Display for DExp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.to_exp(NameStyle::default()), f)
+    }
+}
+impl fmt::Debug for DExp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Var(i) => write!(f, "{}", i),
+            Free(n) => write!(f, "{}", n),
+            Call(a, b) => write!(f, "({:?} {:?})", a, b),
+            Lamb(_, r) => write!(f, "(\\ {:?})", r),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{ parse, ParseError };
+    use crate::reduce::*;
+
+    fn dparse(s: &str) -> Result<DExp, ParseError> {
+        Ok(DExp::from_exp(&parse(s)?))
+    }
+
+    #[test]
+    fn convert() -> Result<(), ParseError> {
+        assert_eq!(format!("{:?}", dparse("\\x y. x")?), "(\\ (\\ 1))");
+        assert_eq!(format!("{:?}", dparse("\\x. x (\\y. y x) z")?), "(\\ ((0 (\\ (0 1))) z))");
+        assert_eq!(format!("{:?}", dparse("\\x x. x")?), "(\\ (\\ 0))");
+        for src in &["x", "\\x y z. x z (y z)", "(\\x. x x) (\\x. x x)", "\\f. f (\\f. f) f"] {
+            assert_eq!(dparse(src)?.to_exp(NameStyle::Prime), parse(src)?);
+        }
+        Ok(())
+    }
+    #[test]
+    fn rename_on_display() -> Result<(), ParseError> {
+        // `\y. x y` with `y` substituted for `x` would capture without renaming
+        let ex = reduce_full(strat_norm, dparse("(\\x y. x y) y")?, Limits::default(), ()).unwrap().0;
+        assert_eq!(format!("{}", ex), "\\y'. y y'");
+        let ex = reduce_full(strat_norm, dparse("(\\x. \\y. \\x. y) z")?, Limits::default(), ()).unwrap().0;
+        assert_eq!(format!("{}", ex), "\\y x. y");
+        let ex = reduce_full(strat_norm, dparse("(\\x y. x y) y")?, Limits::default(), ()).unwrap().0;
+        assert_eq!(ex.to_exp(NameStyle::Subscript), parse("\\y₁. y y₁")?);
+        Ok(())
+    }
+    #[test]
+    fn alpha_equality() -> Result<(), ParseError> {
+        assert_eq!(dparse("\\x. x")?, dparse("\\y. y")?);
+        assert_eq!(dparse("\\x y. x z")?, dparse("\\a b. a z")?);
+        assert_ne!(dparse("\\x y. x")?, dparse("\\x y. y")?);
+        assert_ne!(dparse("\\x. z")?, dparse("\\x. w")?);
+        Ok(())
+    }
+    #[test]
+    fn substitution() -> Result<(), ParseError> {
+        assert_eq!(format!("{:?}", shift(dparse("\\x. x y")?, 1, 0)), "(\\ (0 y))");
+        let ex = DExp::Lamb("x".to_string(), Box::new(Call(Box::new(Var(0)), Box::new(Var(1)))));
+        assert_eq!(format!("{:?}", shift(ex.clone(), 2, 0)), "(\\ (0 3))");
+        assert_eq!(format!("{:?}", super::sub(ex, 0, &Var(4))), "(\\ (0 5))");
+        assert_eq!(reduce_step(strat_norm, dparse("(\\x. \\z. x z) z")?, ()),
+            (Reduc::Beta, dparse("\\w. z w")?));
+        assert_eq!(reduce_step(strat_norm, dparse("\\y. (\\x. \\z. x y z) (\\w. y)")?, ()),
+            (Reduc::Body(Box::new(Reduc::Beta)), dparse("\\y. \\z. (\\w. y) y z")?));
+        assert_eq!(reduce_step(strat_norm_eta, dparse("\\y. \\x. y x")?, ()),
+            (Reduc::Body(Box::new(Reduc::Eta)), dparse("\\y. y")?));
+        Ok(())
+    }
+
+    /// Steps the named and nameless reducers side by side, checking that they
+    /// pick the same redexes and agree on every intermediate term.
+    fn cross_check(strat: Strategy, dstrat: Strategy<DExp>, src: &str) -> Result<(), ParseError> {
+        let limits = Limits { steps: Some(100), ..Limits::default() };
+        let named: Vec<(Reduc, Exp)> = reduce_iter(strat, parse(src)?).limit(limits).collect();
+        let nameless: Vec<(Reduc, DExp)> = reduce_iter(dstrat, dparse(src)?).limit(limits).collect();
+        assert_eq!(named.len(), nameless.len(), "{}", src);
+        for ((r1, e1), (r2, e2)) in named.iter().zip(nameless.iter()) {
+            assert_eq!(r1, r2, "{}", src);
+            assert_eq!(&DExp::from_exp(e1), e2, "{}", src);
+        }
+        Ok(())
+    }
+    #[test]
+    fn cross_check_strategies() -> Result<(), ParseError> {
+        let terms = [
+            "(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)",
+            "(\\a b. a) z ((\\x. x x) (\\x. x x))",
+            "(\\x y. x y) y",
+            "(\\x. \\y. \\x. y x) (x y)",
+            "(\\f x. f (f x)) (\\f x. f (f x))",
+            "\\x. (\\y. f y) x",
+            "let Y := \\f. (\\x. f (x x)) (\\x. f (x x)); \
+             let * := \\m n f x. m (n f) x; \
+             let pred := \\n f x. n (\\g h. h (g f)) (\\u.x) (\\u.u); \
+             let 0? := \\n. n (\\x a b. b) (\\a b. a); \
+             let fac := Y \\fac n. 0? n (\\f x. f x) (* n (fac (pred n))); \
+             fac (\\f x. f (f (f x)))",
+        ];
+        for src in terms.iter() {
+            cross_check(strat_norm, strat_norm, src)?;
+            cross_check(strat_norm_eta, strat_norm_eta, src)?;
+            cross_check(strat_byname, strat_byname, src)?;
+            cross_check(strat_applic, strat_applic, src)?;
+            cross_check(strat_applic_eta, strat_applic_eta, src)?;
+            cross_check(strat_value, strat_value, src)?;
+            cross_check(strat_hnf, strat_hnf, src)?;
+            cross_check(strat_whnf, strat_whnf, src)?;
+        }
+        Ok(())
+    }
+}
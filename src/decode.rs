@@ -0,0 +1,187 @@
+use crate::code::Exp;
+use crate::numeral::{ Encoding, resugar };
+use crate::reduce::free_in;
+use Exp::*;
+
+use std::fmt;
+
+/// Which kinds of data to look for when decoding a term.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Decode {
+    pub numbers: bool,
+    pub booleans: bool,
+    pub pairs: bool,
+    pub lists: bool,
+}
+impl Decode {
+    pub fn all() -> Decode {
+        Decode { numbers: true, booleans: true, pairs: true, lists: true }
+    }
+}
+
+/// Data recognised in a term.
+#[derive(Debug, PartialEq)]
+pub enum Value {
+    Num(u64),
+    Bool(bool),
+    Pair(Box<Value>, Box<Value>),
+    List(Vec<Value>),
+    /// Anything else, with just its numbers shown as digits if those are
+    /// being decoded.
+    Term(Exp),
+}
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Num(n) => write!(f, "{}", n),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Pair(a, b) => write!(f, "({}, {})", a, b),
+            Value::List(xs) => {
+                write!(f, "[")?;
+                for (i, x) in xs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", x)?;
+                }
+                write!(f, "]")
+            }
+            Value::Term(ex) => write!(f, "{}", ex),
+        }
+    }
+}
+
+/// Splits `\a b. body` into its parts, if the two names differ.
+fn lamb2(ex: &Exp) -> Option<(&str, &str, &Exp)> {
+    match ex {
+        Lamb(a, r) => match &**r {
+            Lamb(b, body) if a != b => Some((a, b, body)),
+            _ => None,
+        }
+        _ => None,
+    }
+}
+/// Splits `f a b` into its parts.
+fn call2(ex: &Exp) -> Option<(&Exp, &Exp, &Exp)> {
+    match ex {
+        Call(l, b) => match &**l {
+            Call(f, a) => Some((f, a, b)),
+            _ => None,
+        }
+        _ => None,
+    }
+}
+fn is_var(ex: &Exp, x: &str) -> bool {
+    matches!(ex, Var(y) if y == x)
+}
+
+/// Recognises numbers, booleans, pairs and lists in a term in normal form.
+/// Numbers are in the encoding `enc`; booleans are `\a b. a` and `\a b. b`;
+/// pairs are `\p. p a b`; lists are Church lists, `\c n. c x (c y n)`, or
+/// Scott lists, `\c n. c x (\c n. c y (\c n. n))`. The empty list is the
+/// same term as `false`, and as `0` in most encodings, so is only shown as
+/// `[]` when neither of those is being decoded.
+pub fn decode(ex: &Exp, kinds: Decode, enc: Encoding) -> Value {
+    if kinds.numbers {
+        if let Some(n) = crate::numeral::decode(ex, enc) {
+            return Value::Num(n);
+        }
+    }
+    if kinds.booleans {
+        if let Some((a, b, body)) = lamb2(ex) {
+            if is_var(body, a) || is_var(body, b) {
+                return Value::Bool(is_var(body, a));
+            }
+        }
+    }
+    if kinds.pairs {
+        if let Lamb(p, body) = ex {
+            if let Some((f, a, b)) = call2(body) {
+                if is_var(f, p) && !free_in(p, a) && !free_in(p, b) {
+                    return Value::Pair(Box::new(decode(a, kinds, enc)), Box::new(decode(b, kinds, enc)));
+                }
+            }
+        }
+    }
+    if kinds.lists {
+        if let Some(xs) = church_list(ex).or_else(|| scott_list(ex)) {
+            return Value::List(xs.into_iter().map(|x| decode(x, kinds, enc)).collect());
+        }
+    }
+    Value::Term(if kinds.numbers { resugar(ex, enc) } else { ex.clone() })
+}
+
+fn church_list(ex: &Exp) -> Option<Vec<&Exp>> {
+    let (c, n, mut body) = lamb2(ex)?;
+    let mut xs = Vec::new();
+    while let Some((f, x, rest)) = call2(body) {
+        if !is_var(f, c) || free_in(c, x) || free_in(n, x) {
+            return None;
+        }
+        xs.push(x);
+        body = rest;
+    }
+    if is_var(body, n) { Some(xs) } else { None }
+}
+
+fn scott_list(ex: &Exp) -> Option<Vec<&Exp>> {
+    let mut xs = Vec::new();
+    let mut ex = ex;
+    loop {
+        let (c, n, body) = lamb2(ex)?;
+        if is_var(body, n) {
+            return Some(xs);
+        }
+        let (f, x, rest) = call2(body)?;
+        if !is_var(f, c) || free_in(c, x) || free_in(n, x) {
+            return None;
+        }
+        xs.push(x);
+        ex = rest;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{ parse, ParseError };
+    use crate::numeral::desugar;
+
+    fn show(s: &str, kinds: Decode) -> Result<String, ParseError> {
+        Ok(decode(&desugar(parse(s)?, Encoding::Church), kinds, Encoding::Church).to_string())
+    }
+
+    #[test]
+    fn values() -> Result<(), ParseError> {
+        let all = Decode::all();
+        assert_eq!(show("120", all)?, "120");
+        assert_eq!(show("\\a b. a", all)?, "true");
+        assert_eq!(show("\\p. p 1 (\\a b. a)", all)?, "(1, true)");
+        assert_eq!(show("\\c n. c 1 (c 2 (c 3 n))", all)?, "[1, 2, 3]");
+        assert_eq!(show("\\c n. c 1 (\\c n. c (\\p. p x y) (\\c n. n))", all)?, "[1, (x, y)]");
+        assert_eq!(show("\\c n. c (\\c n. c 4 n) n", all)?, "[[4]]");
+        assert_eq!(show("f 2 (\\x. x)", all)?, "f 2 (\\x. x)");
+        Ok(())
+    }
+
+    #[test]
+    fn ambiguous() -> Result<(), ParseError> {
+        let all = Decode::all();
+        assert_eq!(show("\\a b. b", all)?, "0");
+        assert_eq!(show("\\a b. b", Decode { numbers: false, ..all })?, "false");
+        assert_eq!(show("\\a b. b", Decode { lists: true, ..Decode::default() })?, "[]");
+        assert_eq!(show("\\f x. f x", Decode { booleans: true, ..Decode::default() })?, "\\f x. f x");
+        Ok(())
+    }
+
+    #[test]
+    fn not_values() -> Result<(), ParseError> {
+        let all = Decode::all();
+        // the elements mention the list's own variables
+        assert_eq!(show("\\c n. c n n", all)?, "\\c n. c n n");
+        assert_eq!(show("\\p. p p q", all)?, "\\p. p p q");
+        assert_eq!(show("\\a a. a", all)?, "\\a a. a");
+        assert_eq!(show("2", Decode::default())?, "\\f x. f (f x)");
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+use crate::code::Exp;
+use crate::reduce::{ Reduc, Term, Limits, NameStyle, redexes };
+use Exp::*;
+
+use std::collections::HashMap;
+
+/// Renders a term as a Graphviz syntax tree, with application nodes shown
+/// as `@`. The redex that `red` points to is highlighted, its root more
+/// strongly than the rest of it.
+pub fn tree(ex: &Exp, red: &Reduc) -> String {
+    let mut out = String::from("digraph term {\n    node [shape=circle, fontname=monospace];\n");
+    tree_node(ex, Some(red), false, &mut out, &mut 0);
+    out.push_str("}\n");
+    out
+}
+/// Writes the nodes of `ex` and the edges below it, returning its id.
+/// `red` is the path to the redex from this node, if it is below it.
+fn tree_node(ex: &Exp, red: Option<&Reduc>, in_redex: bool, out: &mut String, next: &mut usize) -> usize {
+    let id = *next;
+    *next += 1;
+    let label = match ex {
+        Var(x) => x.clone(),
+        Call(..) => "@".to_string(),
+        Lamb(x, _) => format!("λ{}", x),
+    };
+    let style = match red {
+        Some(Reduc::Beta) | Some(Reduc::Eta) => ", style=filled, fillcolor=\"#f4a582\", penwidth=2",
+        _ if in_redex => ", style=filled, fillcolor=\"#fddbc7\"",
+        _ => "",
+    };
+    out.push_str(&format!("    n{} [label={}{}];\n", id, quote(&label), style));
+    let in_redex = in_redex || matches!(red, Some(Reduc::Beta) | Some(Reduc::Eta));
+    match ex {
+        Var(_) => {}
+        Call(a, b) => {
+            let (left, right) = match red {
+                Some(Reduc::Left(r)) => (Some(&**r), None),
+                Some(Reduc::Right(r)) => (None, Some(&**r)),
+                _ => (None, None),
+            };
+            let a = tree_node(a, left, in_redex, out, next);
+            let b = tree_node(b, right, in_redex, out, next);
+            out.push_str(&format!("    n{} -> n{};\n    n{} -> n{};\n", id, a, id, b));
+        }
+        Lamb(_, r) => {
+            let body = match red {
+                Some(Reduc::Body(r)) => Some(&**r),
+                _ => None,
+            };
+            let r = tree_node(r, body, in_redex, out, next);
+            out.push_str(&format!("    n{} -> n{};\n", id, r));
+        }
+    }
+    id
+}
+
+/// Renders the reduction graph of a term: every term reachable by
+/// contracting any of its redexes, with an edge for each redex labelled by
+/// the path to it. Alpha equivalent terms are the same node. Stops adding
+/// terms once there are `max_nodes` of them, and leaves out reducts larger
+/// than `limits.size`; terms whose reducts weren't all added are dashed.
+/// Terms in normal form have a double border. Bound variables are renamed
+/// in the style `names`.
+pub fn graph(ex: Exp, eta: bool, limits: Limits, names: NameStyle, max_nodes: usize) -> String {
+    let mut terms = vec![ex];
+    let mut ids: HashMap<u64, Vec<usize>> = HashMap::new();
+    ids.entry(terms[0].alpha_hash()).or_default().push(0);
+    let mut edges = String::new();
+    // whether each term has reducts that were left out
+    let mut partial = vec![false];
+    let mut i = 0;
+    while i < terms.len() {
+        for red in redexes(&terms[i], eta) {
+            let new = terms[i].clone().reduce_with(&red, names);
+            if limits.size.is_some_and(|max| new.size() > max) {
+                partial[i] = true;
+                continue;
+            }
+            let hash = new.alpha_hash();
+            let same = ids.get(&hash).and_then(|ids| ids.iter().copied().find(|&j| terms[j].alpha_eq(&new)));
+            let j = match same {
+                Some(j) => j,
+                None if terms.len() < max_nodes => {
+                    ids.entry(hash).or_default().push(terms.len());
+                    terms.push(new);
+                    partial.push(false);
+                    terms.len() - 1
+                }
+                None => {
+                    partial[i] = true;
+                    continue;
+                }
+            };
+            edges.push_str(&format!("    n{} -> n{} [label={}];\n", i, j, quote(&red.to_string())));
+        }
+        i += 1;
+    }
+    let mut out = String::from("digraph reductions {\n    node [shape=box, fontname=monospace];\n");
+    for (i, ex) in terms.iter().enumerate() {
+        let mut style = Vec::new();
+        if i == 0 {
+            style.push("penwidth=2");
+        }
+        if redexes(ex, eta).is_empty() {
+            style.push("peripheries=2");
+        }
+        if partial[i] {
+            style.push("style=dashed");
+        }
+        let style: String = style.iter().map(|s| format!(", {}", s)).collect();
+        out.push_str(&format!("    n{} [label={}{}];\n", i, quote(&ex.to_string()), style));
+    }
+    out.push_str(&edges);
+    out.push_str("}\n");
+    out
+}
+
+/// Quotes a label for DOT, where backslashes start escape sequences.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{ parse, ParseError };
+    use crate::reduce::strat_norm;
+
+    #[test]
+    fn trees() -> Result<(), ParseError> {
+        let ex = parse("f ((\\x. x) y)")?;
+        assert_eq!(tree(&ex, &strat_norm(&ex)), concat!(
+            "digraph term {\n",
+            "    node [shape=circle, fontname=monospace];\n",
+            "    n0 [label=\"@\"];\n",
+            "    n1 [label=\"f\"];\n",
+            "    n2 [label=\"@\", style=filled, fillcolor=\"#f4a582\", penwidth=2];\n",
+            "    n3 [label=\"λx\", style=filled, fillcolor=\"#fddbc7\"];\n",
+            "    n4 [label=\"x\", style=filled, fillcolor=\"#fddbc7\"];\n",
+            "    n3 -> n4;\n",
+            "    n5 [label=\"y\", style=filled, fillcolor=\"#fddbc7\"];\n",
+            "    n2 -> n3;\n",
+            "    n2 -> n5;\n",
+            "    n0 -> n1;\n",
+            "    n0 -> n2;\n",
+            "}\n",
+        ));
+        assert!(!tree(&ex, &Reduc::Irred).contains("style"));
+        Ok(())
+    }
+
+    #[test]
+    fn graphs() -> Result<(), ParseError> {
+        // both ways of reducing lead to the same terms, up to renaming
+        assert_eq!(graph(parse("(\\x. x) ((\\y. y) z)")?, false, Limits::default(), NameStyle::Prime, 10), concat!(
+            "digraph reductions {\n",
+            "    node [shape=box, fontname=monospace];\n",
+            "    n0 [label=\"(\\\\x. x) ((\\\\y. y) z)\", penwidth=2];\n",
+            "    n1 [label=\"(\\\\y. y) z\"];\n",
+            "    n2 [label=\"z\", peripheries=2];\n",
+            "    n0 -> n1 [label=\"β\"];\n",
+            "    n0 -> n1 [label=\"(_ β)\"];\n",
+            "    n1 -> n2 [label=\"β\"];\n",
+            "}\n",
+        ));
+        let omega = graph(parse("(\\x. x x) (\\x. x x)")?, false, Limits::default(), NameStyle::Prime, 10);
+        assert!(omega.contains("n0 -> n0 [label=\"β\"]"));
+        assert!(!omega.contains("n1"));
+        // the terms keep growing, so the limit cuts the graph off
+        let growing = graph(parse("(\\x. x x x) (\\x. x x x)")?, false, Limits::default(), NameStyle::Prime, 3);
+        assert!(growing.contains("n2 [label=") && !growing.contains("n3"));
+        assert!(growing.contains("style=dashed"));
+        // and so does the size limit, which leaves out the bigger terms
+        let limits = Limits { size: Some(20), ..Limits::default() };
+        let growing = graph(parse("(\\x. x x x) (\\x. x x x)")?, false, limits, NameStyle::Prime, 10);
+        assert!(growing.contains("n1 [label=") && !growing.contains("n2"));
+        assert!(growing.contains("n1 [label=\"(\\\\x. x x x) (\\\\x. x x x) (\\\\x. x x x)\", style=dashed]"));
+        Ok(())
+    }
+}
@@ -0,0 +1,339 @@
+use crate::code::Exp;
+use crate::parser::{ ParseError, PErrType };
+use crate::reduce::Reduc;
+use Exp::*;
+
+/// Encodes a term as JSON: `{"var":"x"}`, `{"app":[fun,arg]}` or
+/// `{"lam":"x","body":body}`.
+pub fn term(ex: &Exp) -> String {
+    let mut out = String::new();
+    write_term(ex, &mut out);
+    out
+}
+fn write_term(ex: &Exp, out: &mut String) {
+    match ex {
+        Var(x) => {
+            out.push_str("{\"var\":");
+            quote(x, out);
+            out.push('}');
+        }
+        Call(a, b) => {
+            out.push_str("{\"app\":[");
+            write_term(a, out);
+            out.push(',');
+            write_term(b, out);
+            out.push_str("]}");
+        }
+        Lamb(x, r) => {
+            out.push_str("{\"lam\":");
+            quote(x, out);
+            out.push_str(",\"body\":");
+            write_term(r, out);
+            out.push('}');
+        }
+    }
+}
+
+/// Encodes a reduction step as JSON: the path to the redex as a list of
+/// `"Left"`, `"Right"` and `"Body"`, the rule applied, and the resulting
+/// term. `None` stands for the expansion of definitions, with an empty path
+/// and the rule `"delta"`. `at` is where the redex is in the source, if known.
+pub fn step(red: Option<&Reduc>, at: Option<&str>, ex: &Exp) -> String {
+    let mut path = Vec::new();
+    let mut rule = "delta";
+    let mut red = red;
+    while let Some(r) = red {
+        red = match r {
+            Reduc::Left(r) => { path.push("\"Left\""); Some(r) }
+            Reduc::Right(r) => { path.push("\"Right\""); Some(r) }
+            Reduc::Body(r) => { path.push("\"Body\""); Some(r) }
+            Reduc::Beta => { rule = "beta"; None }
+            Reduc::Eta => { rule = "eta"; None }
+            Reduc::Irred => { rule = "none"; None }
+        }
+    }
+    let mut out = format!("{{\"path\":[{}],\"rule\":\"{}\",", path.join(","), rule);
+    if let Some(at) = at {
+        out += "\"at\":";
+        quote(at, &mut out);
+        out.push(',');
+    }
+    format!("{}\"term\":{}}}", out, term(ex))
+}
+
+/// Writes `s` as a JSON string.
+pub fn quote(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// A JSON value, with the position each nested value starts at.
+#[derive(Debug, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Num(f64),
+    Str(String),
+    Arr(Vec<(usize, Json)>),
+    Obj(Vec<(String, usize, Json)>),
+}
+
+fn bad<T>(pos: usize) -> Result<T, ParseError> {
+    Err(ParseError { typ: PErrType::BadJson, pos })
+}
+
+/// Parses a term encoded as by `term`. Whitespace is allowed anywhere JSON
+/// allows it, and fields can come in any order.
+pub fn parse_term(input: &str) -> Result<Exp, ParseError> {
+    let inp = input.as_bytes();
+    let mut i = 0;
+    skip_ws(&mut i, inp);
+    let start = i;
+    let json = get_value(&mut i, inp)?;
+    skip_ws(&mut i, inp);
+    if i != inp.len() {
+        return bad(i);
+    }
+    to_term(start, json)
+}
+
+fn to_term(pos: usize, json: Json) -> Result<Exp, ParseError> {
+    let not_term = Err(ParseError { typ: PErrType::NotTerm, pos });
+    let mut fields = match json {
+        Json::Obj(fields) => fields,
+        _ => return not_term,
+    };
+    fields.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut fields = fields.into_iter();
+    match (fields.next(), fields.next(), fields.next()) {
+        (Some((k, _, Json::Str(x))), None, None) if k == "var" => Ok(Var(x)),
+        (Some((k, _, Json::Arr(args))), None, None) if k == "app" && args.len() == 2 => {
+            let mut args = args.into_iter();
+            let (p, a) = args.next().unwrap();
+            let (q, b) = args.next().unwrap();
+            Ok(Call(Box::new(to_term(p, a)?), Box::new(to_term(q, b)?)))
+        }
+        (Some((k, p, body)), Some((l, _, Json::Str(x))), None) if k == "body" && l == "lam" =>
+            Ok(Lamb(x, Box::new(to_term(p, body)?))),
+        _ => not_term,
+    }
+}
+
+fn skip_ws(i: &mut usize, input: &[u8]) {
+    while *i < input.len() && matches!(input[*i], b' ' | b'\n' | b'\r' | b'\t') {
+        *i += 1;
+    }
+}
+
+fn get_value(i: &mut usize, input: &[u8]) -> Result<Json, ParseError> {
+    let start = *i;
+    let lit = |i: &mut usize, word: &[u8], val: Json| {
+        if input[*i..].starts_with(word) {
+            *i += word.len();
+            Ok(val)
+        } else {
+            bad(start)
+        }
+    };
+    match input.get(*i) {
+        Some(b'n') => lit(i, b"null", Json::Null),
+        Some(b't') => lit(i, b"true", Json::Bool(true)),
+        Some(b'f') => lit(i, b"false", Json::Bool(false)),
+        Some(b'"') => get_string(i, input).map(Json::Str),
+        Some(b'[') => {
+            *i += 1;
+            let mut items = Vec::new();
+            skip_ws(i, input);
+            if input.get(*i) == Some(&b']') {
+                *i += 1;
+                return Ok(Json::Arr(items));
+            }
+            loop {
+                skip_ws(i, input);
+                let pos = *i;
+                items.push((pos, get_value(i, input)?));
+                skip_ws(i, input);
+                match input.get(*i) {
+                    Some(b',') => *i += 1,
+                    Some(b']') => {
+                        *i += 1;
+                        return Ok(Json::Arr(items));
+                    }
+                    _ => return bad(*i),
+                }
+            }
+        }
+        Some(b'{') => {
+            *i += 1;
+            let mut fields = Vec::new();
+            skip_ws(i, input);
+            if input.get(*i) == Some(&b'}') {
+                *i += 1;
+                return Ok(Json::Obj(fields));
+            }
+            loop {
+                skip_ws(i, input);
+                if input.get(*i) != Some(&b'"') {
+                    return bad(*i);
+                }
+                let key = get_string(i, input)?;
+                skip_ws(i, input);
+                if input.get(*i) != Some(&b':') {
+                    return bad(*i);
+                }
+                *i += 1;
+                skip_ws(i, input);
+                let pos = *i;
+                fields.push((key, pos, get_value(i, input)?));
+                skip_ws(i, input);
+                match input.get(*i) {
+                    Some(b',') => *i += 1,
+                    Some(b'}') => {
+                        *i += 1;
+                        return Ok(Json::Obj(fields));
+                    }
+                    _ => return bad(*i),
+                }
+            }
+        }
+        Some(b'-') | Some(b'0'..=b'9') => {
+            *i += 1;
+            while *i < input.len() && matches!(input[*i], b'0'..=b'9' | b'.' | b'e' | b'E' | b'+' | b'-') {
+                *i += 1;
+            }
+            match String::from_utf8_lossy(&input[start..*i]).parse() {
+                Ok(n) => Ok(Json::Num(n)),
+                Err(_) => bad(start),
+            }
+        }
+        _ => bad(*i),
+    }
+}
+
+/// Parses the string starting with the `"` at `i`, undoing escapes.
+fn get_string(i: &mut usize, input: &[u8]) -> Result<String, ParseError> {
+    let start = *i;
+    *i += 1;
+    let mut out = Vec::new();
+    loop {
+        match input.get(*i) {
+            None => return bad(start),
+            Some(b'"') => {
+                *i += 1;
+                return String::from_utf8(out).or_else(|_| bad(start));
+            }
+            Some(b'\\') => {
+                let c = match input.get(*i + 1) {
+                    Some(b'"') => '"',
+                    Some(b'\\') => '\\',
+                    Some(b'/') => '/',
+                    Some(b'b') => '\u{8}',
+                    Some(b'f') => '\u{c}',
+                    Some(b'n') => '\n',
+                    Some(b'r') => '\r',
+                    Some(b't') => '\t',
+                    Some(b'u') => {
+                        let esc = *i;
+                        let mut code = get_hex(i, input)?;
+                        // characters outside the BMP are written as surrogate pairs
+                        if (0xd800..0xdc00).contains(&code) && input[*i..].starts_with(b"\\u") {
+                            let low = get_hex(i, input)?;
+                            if !(0xdc00..0xe000).contains(&low) {
+                                return bad(esc);
+                            }
+                            code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                        }
+                        match char::from_u32(code) {
+                            Some(c) => {
+                                out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                                continue;
+                            }
+                            None => return bad(esc),
+                        }
+                    }
+                    _ => return bad(*i),
+                };
+                out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                *i += 2;
+            }
+            Some(&c) if c < 0x20 => return bad(*i),
+            Some(&c) => {
+                out.push(c);
+                *i += 1;
+            }
+        }
+    }
+}
+/// Reads the `\uXXXX` escape at `i`.
+fn get_hex(i: &mut usize, input: &[u8]) -> Result<u32, ParseError> {
+    let start = *i;
+    let digits = input.get(*i + 2..*i + 6).ok_or(ParseError { typ: PErrType::BadJson, pos: start })?;
+    *i += 6;
+    match u32::from_str_radix(&String::from_utf8_lossy(digits), 16) {
+        Ok(code) => Ok(code),
+        Err(_) => bad(start),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::reduce::{ reduce_iter, strat_norm };
+
+    #[test]
+    fn terms() -> Result<(), ParseError> {
+        let ex = parse("(\\x. f x) y")?;
+        let json = term(&ex);
+        assert_eq!(json, r#"{"app":[{"lam":"x","body":{"app":[{"var":"f"},{"var":"x"}]}},{"var":"y"}]}"#);
+        assert_eq!(parse_term(&json)?, ex);
+        // whitespace and field order don't matter
+        assert_eq!(parse_term(" { \"body\" : {\"var\":\"x\"}, \"lam\":\"x\" }\n")?, parse("\\x. x")?);
+        let odd = Var("a \"b\"\\\n\u{1}λ".to_string());
+        assert_eq!(term(&odd), r#"{"var":"a \"b\"\\\n\u0001λ"}"#);
+        assert_eq!(parse_term(&term(&odd))?, odd);
+        assert_eq!(parse_term(r#"{"var":"λ😀\/"}"#)?, Var("λ😀/".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn steps() -> Result<(), ParseError> {
+        let trace: Vec<String> = reduce_iter(strat_norm, parse("\\z. (\\x. x) z")?)
+            .map(|(red, ex)| step(Some(&red), None, &ex))
+            .collect();
+        assert_eq!(trace, vec![r#"{"path":["Body"],"rule":"beta","term":{"lam":"z","body":{"var":"z"}}}"#]);
+        assert_eq!(step(None, None, &parse("x")?), r#"{"path":[],"rule":"delta","term":{"var":"x"}}"#);
+        assert_eq!(step(Some(&Reduc::Beta), Some("a.lb:1:1-1:4"), &parse("x")?),
+            r#"{"path":[],"rule":"beta","at":"a.lb:1:1-1:4","term":{"var":"x"}}"#);
+        Ok(())
+    }
+
+    #[test]
+    fn errors() {
+        let err = |s: &str| parse_term(s).unwrap_err();
+        assert_eq!(err("{\"var\":\"x\""), ParseError { typ: PErrType::BadJson, pos: 10 });
+        assert_eq!(err("{\"var\":\"x\"} x"), ParseError { typ: PErrType::BadJson, pos: 12 });
+        assert_eq!(err("{\"var\":\"x"), ParseError { typ: PErrType::BadJson, pos: 7 });
+        assert_eq!(err("[1, 2.5e3, null, true]"), ParseError { typ: PErrType::NotTerm, pos: 0 });
+        assert_eq!(err("{\"app\":[{\"var\":\"f\"},{\"var\":1}]}"), ParseError { typ: PErrType::NotTerm, pos: 20 });
+        assert_eq!(err("{\"app\":[{\"var\":\"f\"}]}"), ParseError { typ: PErrType::NotTerm, pos: 0 });
+        assert_eq!(err("{\"var\":\"x\",\"lam\":\"y\"}"), ParseError { typ: PErrType::NotTerm, pos: 0 });
+        // a high surrogate must be followed by a low one
+        assert_eq!(err(r#"{"var":"\ud83d\u0041"}"#), ParseError { typ: PErrType::BadJson, pos: 8 });
+        assert_eq!(err(r#"{"var":"\ud83d\ud83d"}"#), ParseError { typ: PErrType::BadJson, pos: 8 });
+        assert_eq!(err(r#"{"var":"\ud83dx"}"#), ParseError { typ: PErrType::BadJson, pos: 8 });
+        assert_eq!(err(r#"{"var":"\ude00"}"#), ParseError { typ: PErrType::BadJson, pos: 8 });
+        assert_eq!(parse_term(r#"{"var":"\ud83d\ude00"}"#), Ok(Var("😀".to_string())));
+    }
+}
@@ -3,96 +3,269 @@ extern crate clap;
 use clap::{ Arg, App };
 
 use std::fs;
-use std::io::{ stdin, stdout, Write };
-use std::time::Instant;
+use std::io::{ self, Read };
+use std::process;
+use std::sync::atomic::{ AtomicBool, Ordering };
 
 mod code;
 mod parser;
-use parser::parse;
 mod reduce;
-use reduce::{ Strategy, reduce_iter, reduce_full, strat_norm, strat_byname };
+use reduce::{ NameStyle, Limits };
+mod debruijn;
+mod session;
+use session::{ Config, Session, Expand, Format, Dot, Status };
+mod types;
+mod repl;
+mod span;
+mod numeral;
+use numeral::Encoding;
+mod decode;
+use decode::Decode;
+mod prelude;
+mod json;
+mod dot;
+use repl::repl;
 
 fn main() {
     let matches = App::new("Lambda")
         .version(crate_version!())
         .author(crate_authors!())
         .about("Evaluates lambda calculus terms")
+        .after_help("EXIT STATUS:\n    0 on success, 1 if the arguments were invalid or the input couldn't be read or parsed,\n    \
+            and 2 if reduction gave up before reaching a normal form")
         .arg(Arg::with_name("STRAT")
             .short("s")
             .long("strat")
             .takes_value(true)
-            .possible_values(&["byname", "normal"])
+            .possible_values(&["byname", "normal", "applicative", "value", "hnf", "whnf"])
             .default_value("normal")
             .help("Sets reduction order")
         )
+        .arg(Arg::with_name("ETA")
+            .long("eta")
+            .help("Also performs eta reductions (only affects normal and applicative order)")
+        )
+        .arg(Arg::with_name("NAMELESS")
+            .long("nameless")
+            .help("Reduces using de Bruijn indices instead of named variables")
+        )
+        .arg(Arg::with_name("NAMES")
+            .long("names")
+            .takes_value(true)
+            .possible_values(&["prime", "number", "subscript"])
+            .default_value("prime")
+            .help("Sets how bound variables are renamed to avoid capture")
+        )
+        .arg(Arg::with_name("EXPAND")
+            .long("expand")
+            .takes_value(true)
+            .possible_values(&["eager", "lazy"])
+            .default_value("eager")
+            .help("Sets whether definitions are substituted before reduction or once the term is stuck")
+        )
+        .arg(Arg::with_name("NUMERALS")
+            .long("numerals")
+            .takes_value(true)
+            .possible_values(&["church", "scott", "parigot"])
+            .default_value("church")
+            .help("Sets how numeric literals like 5 are encoded")
+        )
+        .arg(Arg::with_name("DECODE")
+            .long("decode")
+            .takes_value(true)
+            .value_name("KINDS")
+            .min_values(0)
+            .require_equals(true)
+            .use_delimiter(true)
+            .possible_values(&["all", "numbers", "booleans", "pairs", "lists"])
+            .help("Shows results as numbers, booleans, pairs or lists where possible (all of them if none given)")
+        )
+        .arg(Arg::with_name("NO_PRELUDE")
+            .long("no-prelude")
+            .help("Starts without the built-in definitions of booleans, numbers, lists and combinators")
+        )
         .arg(Arg::with_name("VERBOSE")
             .short("l")
             .long("list")
             .help("Lists individual reduction steps")
         )
+        .arg(Arg::with_name("FORMAT")
+            .long("format")
+            .takes_value(true)
+            .possible_values(&["text", "json"])
+            .default_value("text")
+            .help("Sets how terms and reduction steps are printed; json prints one object per line")
+        )
+        .arg(Arg::with_name("INPUT_FORMAT")
+            .long("input-format")
+            .takes_value(true)
+            .value_name("FORMAT")
+            .possible_values(&["text", "json"])
+            .help("Sets how input is read [default: json for files ending in .json, text otherwise]")
+        )
+        .arg(Arg::with_name("DOT")
+            .long("dot")
+            .takes_value(true)
+            .possible_values(&["tree", "graph"])
+            .help("Prints the syntax tree or the reduction graph of the main expression in Graphviz format, instead of \
+                evaluating it")
+        )
+        .arg(Arg::with_name("MAX_NODES")
+            .long("max-nodes")
+            .takes_value(true)
+            .value_name("N")
+            .default_value("100")
+            .help("Sets the most terms to show in a reduction graph")
+        )
+        .arg(Arg::with_name("QUIET")
+            .short("q")
+            .long("quiet")
+            .conflicts_with("VERBOSE")
+            .help("Only prints the result")
+        )
+        .arg(Arg::with_name("TIMING")
+            .long("timing")
+            .help("Prints how long parsing and reduction take, to stderr")
+        )
+        .arg(Arg::with_name("MAX_STEPS")
+            .long("max-steps")
+            .takes_value(true)
+            .value_name("N")
+            .help("Gives up after N reduction steps [default in the REPL: 1000000]")
+        )
+        .arg(Arg::with_name("MAX_SIZE")
+            .long("max-size")
+            .takes_value(true)
+            .value_name("N")
+            .help("Gives up once the term grows beyond N nodes")
+        )
+        .arg(Arg::with_name("EVAL")
+            .short("e")
+            .long("eval")
+            .takes_value(true)
+            .value_name("EXPR")
+            .help("Evaluates EXPR, after loading the definitions in any INPUT files")
+        )
         .arg(Arg::with_name("INPUT")
-            .help("Sets the source file to use, or if none given, launches a REPL")
+            .multiple(true)
+            .help("Sets the source files to use, or - for standard input. Their definitions are loaded in order, and \
+                the last main expression is evaluated. If none are given, and no EXPR, launches a REPL")
         )
     .get_matches();
-    let strat = match matches.value_of("STRAT") {
-        Some("byname") => strat_byname,
-        Some("normal") => strat_norm,
-        _ => panic!("invalid strategy")
-    };
+    let strat = matches.value_of("STRAT").unwrap().to_string();
+    let eta = matches.is_present("ETA");
+    let nameless = matches.is_present("NAMELESS");
     let verbose = matches.is_present("VERBOSE");
-    if let Some(file) = matches.value_of("INPUT") {
-        let inp = fs::read_to_string(file).expect("error loading file");
-        run(&inp, strat, verbose);
-    } else {
-        println!("Lambda v{}", crate_version!());
-        loop {
-            print!("λ> ");
-            stdout().flush().expect("error flushing stdin");
-            let mut inp = String::new();
-            stdin().read_line(&mut inp).expect("error reading stdin");
-            run(&inp, strat, verbose);
+    let quiet = matches.is_present("QUIET");
+    let timing = matches.is_present("TIMING");
+    let dot = match matches.value_of("DOT") {
+        Some("tree") => Some(Dot::Tree),
+        Some(_) => Some(Dot::Graph(value_t!(matches, "MAX_NODES", usize).unwrap_or_else(|e| e.exit()))),
+        None => None,
+    };
+    let format = match matches.value_of("FORMAT") {
+        Some("json") => Format::Json,
+        _ => Format::Text,
+    };
+    let input = match matches.value_of("INPUT_FORMAT") {
+        Some("json") => Some(Format::Json),
+        Some(_) => Some(Format::Text),
+        None => None,
+    };
+    let mut limits = Limits {
+        steps: limit_arg(&matches, "MAX_STEPS"),
+        size: limit_arg(&matches, "MAX_SIZE"),
+        interrupt: None,
+    };
+    let names = match matches.value_of("NAMES") {
+        Some("number") => NameStyle::Number,
+        Some("subscript") => NameStyle::Subscript,
+        _ => NameStyle::Prime,
+    };
+    let expand = match matches.value_of("EXPAND") {
+        Some("lazy") => Expand::Lazy,
+        _ => Expand::Eager,
+    };
+    let numerals = match matches.value_of("NUMERALS") {
+        Some("scott") => Encoding::Scott,
+        Some("parigot") => Encoding::Parigot,
+        _ => Encoding::Church,
+    };
+    let decode = match matches.values_of("DECODE") {
+        Some(kinds) => {
+            let kinds: Vec<&str> = kinds.collect();
+            let has = |kind| kinds.is_empty() || kinds.contains(&"all") || kinds.contains(&kind);
+            Decode { numbers: has("numbers"), booleans: has("booleans"), pairs: has("pairs"), lists: has("lists") }
         }
+        None if matches.is_present("DECODE") => Decode::all(),
+        None => Decode::default(),
     };
-}
-
-fn run(inp: &str, strat: Strategy, verbose: bool) {
-    let now = Instant::now();
-    let p = parse(&inp);
-    println!("Parse time: {:.3}ms", now.elapsed().as_millis() as f64 * 1e-3);
-
-    match p {
-        Ok(ex) => {
-            println!("{}", ex);
-            let now = Instant::now();
-            if verbose {
-                for (red, ex) in reduce_iter(strat, ex) {
-                    println!("=={}==>", red);
-                    println!("{}", ex);
-                }
+    let files: Vec<&str> = matches.values_of("INPUT").map_or(Vec::new(), Iterator::collect);
+    let eval = matches.value_of("EVAL");
+    let interactive = files.is_empty() && eval.is_none();
+    let mut max_listed = None;
+    if interactive {
+        // Ctrl-C aborts the current reduction rather than the whole session
+        ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst))
+            .expect("error setting Ctrl-C handler");
+        limits.interrupt = Some(&INTERRUPTED);
+        // so that a diverging term doesn't hang the REPL until Ctrl-C
+        limits.steps = limits.steps.or(Some(REPL_MAX_STEPS));
+        // and so that listing one doesn't flood the terminal
+        max_listed = Some(REPL_MAX_LISTED);
+    }
+    let mut session = Session::new(Config {
+        strat, eta, nameless, verbose, max_listed, limits, names, expand, numerals, decode, quiet, timing, format,
+        input, dot
+    });
+    if !matches.is_present("NO_PRELUDE") {
+        session.load_prelude();
+    }
+    if !interactive {
+        let mut texts = Vec::new();
+        for &file in &files {
+            let text = if file == "-" {
+                let mut text = String::new();
+                io::stdin().read_to_string(&mut text).map(|_| text)
             } else {
-                println!("{}", reduce_full(strat, ex));
+                fs::read_to_string(file)
+            };
+            match text {
+                Ok(text) => texts.push(text),
+                Err(e) => {
+                    eprintln!("Couldn't read {}: {}", file, e);
+                    process::exit(EXIT_BAD_INPUT);
+                }
             }
-            println!("Eval time: {:.6}s", now.elapsed().as_micros() as f64 * 1e-6);
-        }
-        Err(e) => {
-            eprintln!("Parse error: {:?} at {:?}", e.typ, rowcol(e.pos, &inp));
         }
-    }
+        // standard input has no file for imports to be relative to
+        let mut sources: Vec<(&str, Option<&str>)> = texts.iter().zip(&files)
+            .map(|(text, &file)| (text.as_str(), Some(file).filter(|&f| f != "-")))
+            .collect();
+        sources.extend(eval.map(|ex| (ex, None)));
+        process::exit(match session.run(&sources) {
+            Status::Success => 0,
+            Status::ParseError => EXIT_BAD_INPUT,
+            Status::GaveUp => EXIT_GAVE_UP,
+        });
+    } else {
+        println!("Lambda v{}", crate_version!());
+        repl(&mut session, &INTERRUPTED);
+    };
 }
 
-fn rowcol(i: usize, s: &str) -> (usize, usize) {
-    let mut row = 0;
-    let mut col = 0;
-    let mut j = 0;
-    let b = s.as_bytes();
-    while j <= i && j < b.len() {
-        if b[j] == b'\n' {
-            row += 1;
-            col = 0;
-        } else {
-            col += 1;
-        }
-        j += 1;
+/// Also what clap exits with on a usage error.
+const EXIT_BAD_INPUT: i32 = 1;
+const EXIT_GAVE_UP: i32 = 2;
+const REPL_MAX_STEPS: usize = 1_000_000;
+const REPL_MAX_LISTED: usize = 1000;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+fn limit_arg(matches: &clap::ArgMatches, name: &str) -> Option<usize> {
+    if matches.is_present(name) {
+        Some(value_t!(matches, name, usize).unwrap_or_else(|e| e.exit()))
+    } else {
+        None
     }
-    (row, col)
-}
\ No newline at end of file
+}
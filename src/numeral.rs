@@ -0,0 +1,209 @@
+use crate::code::Exp;
+use Exp::*;
+
+/// Ways of representing natural numbers as lambda terms.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Encoding {
+    /// `n` is `\f x. f (f (... x))`, applying `f` n times.
+    Church,
+    /// `0` is `\s z. z`, and `n+1` is `\s z. s n`.
+    Scott,
+    /// `0` is `\s z. z`, and `n+1` is `\s z. s n (n s z)`, in normal form.
+    /// Supports both iteration and cheap predecessor, but the terms grow
+    /// exponentially.
+    Parigot,
+}
+
+fn var(x: &str) -> Box<Exp> {
+    Box::new(Var(x.to_string()))
+}
+fn lamb2(a: &str, b: &str, body: Exp) -> Exp {
+    Lamb(a.to_string(), Box::new(Lamb(b.to_string(), Box::new(body))))
+}
+
+/// Builds the term representing `n`.
+pub fn encode(n: u64, enc: Encoding) -> Exp {
+    match enc {
+        Encoding::Church => lamb2("f", "x", (0..n).fold(Var("x".to_string()), |ex, _| Call(var("f"), Box::new(ex)))),
+        Encoding::Scott => (0..n).fold(lamb2("s", "z", Var("z".to_string())), |m, _| {
+            lamb2("s", "z", Call(var("s"), Box::new(m)))
+        }),
+        Encoding::Parigot => {
+            // the body of `n s z` once reduced: s (n-1) (s (n-2) (... (s 0 z)))
+            let body = (0..n).fold(Var("z".to_string()), |body, k| {
+                Call(Box::new(Call(var("s"), Box::new(encode(k, enc)))), Box::new(body))
+            });
+            lamb2("s", "z", body)
+        }
+    }
+}
+
+/// Recognises a term representing a number. Only terms in normal form are
+/// recognised.
+pub fn decode(ex: &Exp, enc: Encoding) -> Option<u64> {
+    let (s, z, body) = match ex {
+        Lamb(s, r) => match &**r {
+            Lamb(z, body) if s != z => (s, z, body),
+            _ => return None,
+        }
+        _ => return None,
+    };
+    match enc {
+        Encoding::Church => {
+            let mut n = 0;
+            let mut ex = &**body;
+            loop {
+                match ex {
+                    Var(x) if x == z => return Some(n),
+                    Call(f, r) if matches!(&**f, Var(x) if x == s) => {
+                        n += 1;
+                        ex = r;
+                    }
+                    _ => return None,
+                }
+            }
+        }
+        Encoding::Scott => match &**body {
+            Var(x) if x == z => Some(0),
+            Call(f, m) if matches!(&**f, Var(x) if x == s) => decode(m, enc).map(|n| n + 1),
+            _ => None,
+        }
+        Encoding::Parigot => {
+            let mut preds = Vec::new();
+            let mut ex = &**body;
+            loop {
+                match ex {
+                    Var(x) if x == z => break,
+                    Call(a, rest) => match &**a {
+                        Call(f, m) if matches!(&**f, Var(x) if x == s) => {
+                            preds.push(m);
+                            ex = rest;
+                        }
+                        _ => return None,
+                    }
+                    _ => return None,
+                }
+            }
+            let n = preds.len();
+            let ok = preds.iter().enumerate().all(|(i, m)| decode(m, enc) == Some((n - 1 - i) as u64));
+            if ok { Some(n as u64) } else { None }
+        }
+    }
+}
+
+/// The largest numeric literal allowed in source. The walks over terms
+/// recurse once per level, and a debug build overflows the main thread's
+/// stack somewhere past 5000 levels, so this leaves room for the sum of two
+/// literals.
+pub const MAX_LITERAL: u64 = 1000;
+
+/// Checks whether a name is a numeric literal.
+pub fn is_literal(x: &str) -> bool {
+    !x.is_empty() && x.bytes().all(|c| c.is_ascii_digit())
+}
+
+/// Replaces free variables whose names are numeric literals, like `5`, with
+/// the numbers they stand for. Parigot numerals grow exponentially, so they
+/// are written as repeated successors of 0 instead, which reduce to the
+/// normal form `encode` builds under the usual limits on reduction.
+pub fn desugar(ex: Exp, enc: Encoding) -> Exp {
+    desugar_under(ex, enc, &mut Vec::new())
+}
+fn desugar_under(ex: Exp, enc: Encoding, bound: &mut Vec<String>) -> Exp {
+    match ex {
+        Var(x) if is_literal(&x) && !bound.contains(&x) => match x.parse() {
+            Ok(n) if enc == Encoding::Parigot => successors(n),
+            Ok(n) => encode(n, enc),
+            Err(_) => Var(x),
+        }
+        Var(x) => Var(x),
+        Call(a, b) => Call(Box::new(desugar_under(*a, enc, bound)), Box::new(desugar_under(*b, enc, bound))),
+        Lamb(x, r) => {
+            bound.push(x);
+            let r = desugar_under(*r, enc, bound);
+            let x = bound.pop().unwrap();
+            Lamb(x, Box::new(r))
+        }
+    }
+}
+
+/// `succ (succ (... 0))` for Parigot numerals, with `n` applications.
+fn successors(n: u64) -> Exp {
+    let succ = Lamb("n".to_string(), Box::new(lamb2("s", "z", Call(
+        Box::new(Call(var("s"), var("n"))),
+        Box::new(Call(Box::new(Call(var("n"), var("s"))), var("z"))),
+    ))));
+    (0..n).fold(encode(0, Encoding::Parigot), |m, _| Call(Box::new(succ.clone()), Box::new(m)))
+}
+
+/// Replaces every subterm that represents a number with a variable named by
+/// its digits, the reverse of `desugar`.
+pub fn resugar(ex: &Exp, enc: Encoding) -> Exp {
+    if let Some(n) = decode(ex, enc) {
+        return Var(n.to_string());
+    }
+    match ex {
+        Var(x) => Var(x.clone()),
+        Call(a, b) => Call(Box::new(resugar(a, enc)), Box::new(resugar(b, enc))),
+        Lamb(x, r) => Lamb(x.clone(), Box::new(resugar(r, enc))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{ parse, ParseError };
+    use crate::reduce::{ Limits, NameStyle, reduce_full, strat_norm };
+    use Encoding::*;
+
+    #[test]
+    fn encodings() -> Result<(), ParseError> {
+        assert_eq!(encode(3, Church), parse("\\f x. f (f (f x))")?);
+        assert_eq!(encode(0, Scott), parse("\\s z. z")?);
+        assert_eq!(encode(2, Scott), parse("\\s z. s (\\s z. s (\\s z. z))")?);
+        assert_eq!(encode(2, Parigot), parse("\\s z. s (\\s z. s (\\s z. z) z) (s (\\s z. z) z)")?);
+        Ok(())
+    }
+
+    #[test]
+    fn round_trip() {
+        for &enc in &[Church, Scott, Parigot] {
+            for n in 0..8 {
+                assert_eq!(decode(&encode(n, enc), enc), Some(n));
+            }
+        }
+        assert_eq!(decode(&encode(2, Scott), Church), None);
+        assert_eq!(decode(&encode(2, Church), Scott), None);
+    }
+
+    #[test]
+    fn decodes_results() -> Result<(), ParseError> {
+        // n+1 = \s z. s n (n s z) reduces to the normal form encode builds
+        let succ = "\\n s z. s n (n s z)";
+        let three = parse(&format!("({0}) (({0}) (({0}) (\\s z. z)))", succ))?;
+        assert_eq!(decode(&reduce_full(strat_norm, three, Limits::default(), NameStyle::Prime).unwrap().0, Parigot), Some(3));
+        let mul = desugar(parse("(\\m n f. m (n f)) 3 4")?, Church);
+        assert_eq!(decode(&reduce_full(strat_norm, mul, Limits::default(), NameStyle::Prime).unwrap().0, Church), Some(12));
+        assert_eq!(decode(&parse("\\x x. x")?, Church), None);
+        assert_eq!(decode(&parse("\\f x. f (g x)")?, Church), None);
+        Ok(())
+    }
+
+    #[test]
+    fn parigot_literals() -> Result<(), ParseError> {
+        // kept small until reduced
+        let ex = desugar(parse("5")?, Parigot);
+        assert!(ex.size() < 100);
+        assert_eq!(reduce_full(strat_norm, ex, Limits::default(), NameStyle::Prime).unwrap().0, encode(5, Parigot));
+        assert!(desugar(parse("1000")?, Parigot).size() < 20_000);
+        Ok(())
+    }
+
+    #[test]
+    fn literals() -> Result<(), ParseError> {
+        assert_eq!(desugar(parse("f 2 (\\2. 2) x1")?, Church),
+            parse("f (\\f x. f (f x)) (\\2. 2) x1")?);
+        assert_eq!(resugar(&parse("f (\\f x. f (f x)) (\\a b. b)")?, Church), parse("f 2 0")?);
+        Ok(())
+    }
+}
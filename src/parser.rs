@@ -1,6 +1,11 @@
 use crate::code::Exp;
+use crate::numeral::{ is_literal, MAX_LITERAL };
+use crate::reduce::{ NameStyle, sub };
+use crate::span::{ Span, SpanTree, Sources, line_col };
 use Exp::*;
 
+use std::fs;
+use std::path::{ Path, PathBuf };
 use std::str;
 
 #[derive(Debug, PartialEq, Eq)]
@@ -11,6 +16,79 @@ pub struct ParseError {
 fn p_err<T>(typ: PErrType, pos: usize) -> Result<T, ParseError> {
     Err(ParseError { typ, pos })
 }
+impl ParseError {
+    /// Renders the error for people: what went wrong, the line of `src` it
+    /// is on with a caret under the position, and a hint if there is one.
+    /// `file` names where `src` came from, if anywhere.
+    pub fn report(&self, src: &str, file: Option<&str>) -> String {
+        // errors at the very end point just past the last thing written
+        let pos = self.pos.min(src.trim_end().len());
+        let (line, col) = line_col(pos, src);
+        let text = src.lines().nth(line - 1).unwrap_or("");
+        let gutter = " ".repeat(line.to_string().len());
+        let mut out = format!("error: {}\n", self.typ.message());
+        out += &format!("{}--> {}{}:{}\n", gutter, file.map_or(String::new(), |f| format!("{}:", f)), line, col);
+        out += &format!("{} |\n", gutter);
+        out += &format!("{} | {}\n", line, text);
+        out += &format!("{} | {}^\n", gutter, " ".repeat(col - 1));
+        let rest = &src.as_bytes()[pos.min(src.len())..];
+        let hint = match self.typ {
+            Reserved if rest.starts_with(b";") =>
+                Some("`;` is only allowed after a let binding or a definition"),
+            Reserved if rest.starts_with(b".") =>
+                Some("`.` is only allowed after the parameters of a lambda"),
+            Reserved if rest.starts_with(b":=") =>
+                Some("`:=` is only allowed in a `let`, or at the start of a line to make a definition"),
+            _ => self.typ.hint(),
+        };
+        if let Some(hint) = hint {
+            out += &format!("{} = hint: {}\n", gutter, hint);
+        }
+        out
+    }
+}
+impl PErrType {
+    /// Explains the error in a few words.
+    pub fn message(&self) -> &'static str {
+        match self {
+            EmptyCall => "expected a term",
+            CloseEarly => "unmatched `)`",
+            NoClose => "expected `)`",
+            Incomplete => "couldn't parse the rest of the input",
+            EmptyArgs => "lambda has no parameters",
+            BadArgs => "expected a parameter name or `.`",
+            BadLet => "missing `:=` after let name",
+            Reserved => "unexpected reserved symbol",
+            DefAfterMain => "definition after the main expression",
+            NoCommentClose => "unclosed block comment",
+            NoQuoteClose => "unclosed file name",
+            NoImport => "couldn't read the imported file",
+            ImportCycle => "import cycle",
+            BadJson => "invalid JSON",
+            NotTerm => "JSON value isn't a term",
+            BigLiteral => "numeric literal is too large",
+        }
+    }
+    /// Suggests how to fix the error, where there's an obvious way.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            EmptyCall => Some("parentheses, and the bodies of lambdas, `let`s and definitions, can't be empty"),
+            CloseEarly => Some("there is no `(` for this to close"),
+            NoClose => Some("some `(` is never closed"),
+            EmptyArgs => Some("write at least one name between the `\\` and the `.`"),
+            BadArgs => Some("parameter names can't contain `(`, `)`, `\\`, `λ`, `;` or `:=`"),
+            BadLet => Some("write `let name := value; body`"),
+            DefAfterMain => Some("a file can only have one main expression, after all of its definitions and imports"),
+            NoCommentClose => Some("close it with `|#`; block comments nest, so each `#|` needs its own `|#`"),
+            NoQuoteClose => Some("write the file name in double quotes on one line, like `import \"lists.lb\"`"),
+            NoImport => Some("imports are relative to the file they are in"),
+            ImportCycle => Some("this file is already being imported; move the definitions both files need into a third file"),
+            NotTerm => Some("terms are {\"var\": name}, {\"app\": [term, term]} or {\"lam\": name, \"body\": term}"),
+            BigLiteral => Some("names made only of digits are numbers, which can be at most 1000"),
+            Incomplete | Reserved | BadJson => None,
+        }
+    }
+}
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum PErrType {
@@ -21,13 +99,21 @@ pub enum PErrType {
     EmptyArgs,
     BadArgs,
     BadLet,
-    Reserved
+    Reserved,
+    DefAfterMain,
+    NoCommentClose,
+    NoQuoteClose,
+    NoImport,
+    ImportCycle,
+    BadJson,
+    NotTerm,
+    BigLiteral
 }
 use PErrType::*;
 
 #[derive(Debug, PartialEq, Eq)]
 enum PCtx {
-    Paren, Root, Fun, Let
+    Paren, Root, Fun, Let, Def
 }
 
 const LAM_HI: u8 = b'\xce';
@@ -36,7 +122,7 @@ fn is_space(c: u8) -> bool {
     c == b' ' || c == b'\n' || c == b'\r' || c == b'\t'
 }
 fn skip_space(i: &mut usize, input: &[u8]) {
-    while is_space(input[*i]) {
+    while *i < input.len() && is_space(input[*i]) {
         *i += 1;
     }
 }
@@ -55,36 +141,444 @@ fn check_seq(i: &usize, input: &[u8], seq: &[u8]) -> bool {
         &input[*i..(i+seq.len())] == seq
 
 }
-fn push_call(ex: Option<Exp>, new: Exp) -> Option<Exp> {
+/// Checks whether the text at `i` starts a top-level definition, `name :=`,
+/// possibly after blank lines and comments.
+fn is_def(i: &usize, input: &[u8]) -> bool {
+    let mut j = *i;
+    skip_blank(&mut j, input);
+    if j == input.len() || is_reserved(&j, input) {
+        return false;
+    }
+    get_var(&mut j, input);
+    while j < input.len() && (input[j] == b' ' || input[j] == b'\t') {
+        j += 1;
+    }
+    check_seq(&j, input, b":=")
+}
+/// Checks whether the text at `i` starts an import, `import "file"`,
+/// possibly after blank lines and comments.
+fn is_import(i: &usize, input: &[u8]) -> bool {
+    let mut j = *i;
+    skip_blank(&mut j, input);
+    if !check_seq(&j, input, b"import") {
+        return false;
+    }
+    j += 6;
+    let start = j;
+    while j < input.len() && (input[j] == b' ' || input[j] == b'\t') {
+        j += 1;
+    }
+    j > start && j < input.len() && input[j] == b'"'
+}
+/// Parses an import starting at `i`, which must be one according to
+/// `is_import`. Only a `;` or a comment may follow it on the same line.
+fn get_import(i: &mut usize, input: &[u8]) -> Result<Import, ParseError> {
+    skip_blank(i, input);
+    while input[*i] != b'"' {
+        *i += 1;
+    }
+    let start = *i;
+    *i += 1;
+    while *i < input.len() && input[*i] != b'"' && input[*i] != b'\n' {
+        *i += 1;
+    }
+    if *i == input.len() || input[*i] != b'"' {
+        return p_err(NoQuoteClose, start);
+    }
+    *i += 1;
+    let path = String::from_utf8_lossy(&input[start+1..*i-1]).into_owned();
+    let span = Span::new(start, *i);
+    while *i < input.len() && (input[*i] == b' ' || input[*i] == b'\t' || input[*i] == b'\r') {
+        *i += 1;
+    }
+    if *i < input.len() && input[*i] == b';' {
+        *i += 1;
+        return Ok(Import { path, span });
+    }
+    // what comes after is left for the doc comment of the next definition
+    let mut next = *i;
+    skip_blank(&mut next, input);
+    if next < input.len() && input[next-1] != b'\n' {
+        return p_err(Incomplete, next);
+    }
+    Ok(Import { path, span })
+}
+/// Finds where a definition starting at `i` ends: the first line break,
+/// outside of parentheses, that is followed by a line starting in the first
+/// column. Indented lines continue the definition.
+fn def_end(i: &usize, input: &[u8]) -> usize {
+    let mut j = *i;
+    let mut depth = 0;
+    while j < input.len() {
+        match input[j] {
+            b'#' => {
+                j = comment_end(&j, input).unwrap_or(input.len());
+                continue;
+            }
+            b'(' => depth += 1,
+            b')' if depth > 0 => depth -= 1,
+            b'\n' if depth == 0 => {
+                let mut k = j;
+                skip_blank(&mut k, input);
+                if k == input.len() || input[k-1] == b'\n' {
+                    return j;
+                }
+            }
+            _ => {}
+        }
+        j += 1;
+    }
+    j
+}
+fn skip_blank(i: &mut usize, input: &[u8]) {
+    while *i < input.len() {
+        if is_space(input[*i]) {
+            *i += 1;
+        } else if input[*i] == b'#' {
+            // an unclosed block comment is reported once parsing reaches it
+            if get_comment(i, input).is_err() {
+                break;
+            }
+        } else {
+            break;
+        }
+    }
+}
+/// An expression along with where its nodes came from.
+pub type Parsed = (Exp, SpanTree);
+
+fn push_call(ex: Option<Parsed>, new: Parsed) -> Option<Parsed> {
     Some(match ex {
-        Some(ex) => Call(Box::new(ex), Box::new(new)),
+        Some((ex, sp)) => {
+            let span = sp.span().to(new.1.span());
+            (Call(Box::new(ex), Box::new(new.0)), SpanTree::Call(span, Box::new(sp), Box::new(new.1)))
+        }
         None => new,
     })
 }
 
 pub fn parse(input: &str) -> Result<Exp, ParseError> {
+    parse_spanned(input).map(|(ex, _)| ex)
+}
+
+/// Like `parse`, but also returns where each node of the expression came from.
+pub fn parse_spanned(input: &str) -> Result<Parsed, ParseError> {
+    let (ex, errors) = parse_partial(input);
+    match errors.into_iter().next() {
+        None => Ok(ex),
+        Some(e) => Err(e),
+    }
+}
+
+/// Parses as much of an expression as it can, returning every error found.
+/// The parts that failed to parse are `SpanTree::Error` nodes in the tree,
+/// with `HOLE` variables in their place in the expression.
+pub fn parse_partial(input: &str) -> (Parsed, Vec<ParseError>) {
+    let mut errors = Vec::new();
+    let ex = get_parse(&mut 0, input.as_bytes(), PCtx::Root, &mut errors);
+    (ex, errors)
+}
+
+/// A file of top-level definitions, `name := expr`, each ending at a `;` or
+/// at the next line that is not indented, and imports of other files,
+/// followed by an optional main expression.
+#[derive(Debug, PartialEq, Default)]
+pub struct Module {
+    pub imports: Vec<Import>,
+    pub defs: Vec<Def>,
+    pub main: Option<Exp>,
+    /// Where the main expression came from.
+    pub main_spans: Option<SpanTree>,
+}
+#[derive(Debug, PartialEq, Clone)]
+pub struct Def {
+    pub name: String,
+    pub val: Exp,
+    /// Where each part of `val` came from.
+    pub spans: SpanTree,
+    /// The `##` doc comment right before the definition.
+    pub doc: Option<String>
+}
+/// An `import "file"` line, which pulls in the definitions of another file.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Import {
+    pub path: String,
+    /// Where the quoted path is, for reporting problems with the file.
+    pub span: Span
+}
+impl Module {
+    /// Moves every span in the definitions and main expression `by` bytes
+    /// along, as `Sources::add` asks.
+    pub fn shift(&mut self, by: usize) {
+        self.defs = self.defs.drain(..).map(|d| Def { spans: d.spans.shift(by), ..d }).collect();
+        self.main_spans = self.main_spans.take().map(|spans| spans.shift(by));
+    }
+    /// Substitutes the definitions into the main expression. Later
+    /// definitions may refer to earlier ones, like a chain of `let`s.
+    #[cfg(test)]
+    pub fn inline(&self) -> Option<Exp> {
+        Some(inline(self.main.clone()?, &self.defs, NameStyle::default()))
+    }
+}
+
+/// Substitutes a sequence of definitions into `ex`, as if each were bound by
+/// a `let` around the ones after it. Binders are renamed in the style `names`
+/// where they would capture.
+pub fn inline(ex: Exp, defs: &[Def], names: NameStyle) -> Exp {
+    defs.iter().rev().fold(ex, |ex, d| sub(ex, &d.name, &d.val, names))
+}
+
+/// A top-level piece of a file, as found by `parse_items`.
+#[derive(Debug, PartialEq)]
+enum Item {
+    Import(Import),
+    Def(Def),
+    Main(Parsed),
+    /// Something that failed to parse.
+    Error(ParseError)
+}
+
+/// Splits a file into its imports, definitions and main expression, carrying
+/// on after errors so that every problem can be reported at once. A
+/// definition or main expression that fails to parse becomes an
+/// `Item::Error` for each of its errors. Parsing resumes within it after
+/// the next `;` or `)`, and after a broken import at the next line.
+fn parse_items(input: &str) -> Vec<Item> {
     let mut i = 0;
     let inp = input.as_bytes();
-    let ex = get_parse(&mut i, inp, PCtx::Root)?;
-    if i == inp.len() {
-        Ok(ex)
-    } else {
-        p_err(Incomplete, i)
+    let mut items = Vec::new();
+    let mut main = false;
+    loop {
+        let before = i;
+        let doc = skip_blank_doc(&mut i, inp);
+        if i == inp.len() {
+            break;
+        }
+        let mut errors = Vec::new();
+        let item = if is_import(&i, inp) {
+            if main {
+                items.push(Item::Error(ParseError { typ: DefAfterMain, pos: i }));
+            }
+            let end = inp[i..].iter().position(|&c| c == b'\n').map_or(inp.len(), |n| i + n);
+            get_import(&mut i, inp).map(Item::Import).unwrap_or_else(|e| {
+                i = end;
+                Item::Error(e)
+            })
+        } else if is_def(&i, inp) {
+            if main {
+                items.push(Item::Error(ParseError { typ: DefAfterMain, pos: i }));
+            }
+            let name = get_var(&mut i, inp);
+            skip_space(&mut i, inp);
+            i += 2;
+            // a line starting another definition can't be part of this one,
+            // even if an unclosed parenthesis says otherwise
+            let end = def_end(&i, inp).min(next_def_line(&i, inp));
+            let (val, spans) = get_parse(&mut i, &inp[..end], PCtx::Def, &mut errors);
+            Item::Def(Def { name, val, spans, doc })
+        } else {
+            main = true;
+            let end = next_def_line(&i, inp);
+            // parsing from before the doc comment gives it to a `let` that
+            // starts the expression
+            i = before;
+            Item::Main(get_parse(&mut i, &inp[..end], PCtx::Root, &mut errors))
+        };
+        if errors.is_empty() {
+            items.push(item);
+        } else {
+            items.extend(errors.into_iter().map(Item::Error));
+        }
     }
+    items
+}
+/// Finds the next line break after `i` that is followed by a definition or
+/// import in the first column, or the end of the input.
+fn next_def_line(i: &usize, input: &[u8]) -> usize {
+    let mut j = *i;
+    while j < input.len() {
+        if input[j] == b'#' {
+            j = comment_end(&j, input).unwrap_or(input.len());
+            continue;
+        }
+        if input[j] == b'\n' && j + 1 < input.len() && !is_space(input[j+1]) &&
+            (is_def(&(j+1), input) || is_import(&(j+1), input)) {
+            return j;
+        }
+        j += 1;
+    }
+    j
+}
+impl Module {
+    /// Collects the items of a file, or all of their errors if there are any.
+    fn from_items(items: Vec<Item>) -> Result<Module, Vec<ParseError>> {
+        let mut m = Module::default();
+        let mut errors = Vec::new();
+        for item in items {
+            match item {
+                Item::Import(imp) => m.imports.push(imp),
+                Item::Def(d) => m.defs.push(d),
+                Item::Main((ex, spans)) => {
+                    m.main = Some(ex);
+                    m.main_spans = Some(spans);
+                }
+                Item::Error(e) => errors.push(e),
+            }
+        }
+        if errors.is_empty() {
+            Ok(m)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Parses a whole file, reporting every error found rather than only the
+/// first.
+pub fn parse_file(input: &str) -> Result<Module, Vec<ParseError>> {
+    Module::from_items(parse_items(input))
 }
 
-fn get_parse(i: &mut usize, input: &[u8], ctx: PCtx) -> Result<Exp, ParseError> {
-    let mut ex: Option<Exp> = None;
+/// Parse errors in one of the files making up a program, along with its
+/// source, so that they can be reported.
+#[derive(Debug)]
+pub struct FileErrors {
+    pub file: Option<String>,
+    pub src: String,
+    pub errors: Vec<ParseError>
+}
+impl FileErrors {
+    pub fn report(&self) -> String {
+        self.errors.iter().map(|e| e.report(&self.src, self.file.as_deref())).collect()
+    }
+}
+
+/// Parses a file like `parse_file`, along with the files it imports and the
+/// ones they import in turn. `file` is where `input` came from, if anywhere;
+/// imports are relative to the importing file, or the current directory for
+/// input that isn't from a file. Imported definitions come before the
+/// file's own, in the order they are imported, and a file imported more
+/// than once is only loaded the first time. The main expressions of
+/// imported files are ignored. The text of each file is added to `sources`,
+/// and the spans in the module point into it.
+pub fn load_file(input: &str, file: Option<&Path>, sources: &mut Sources) -> Result<Module, Vec<FileErrors>> {
+    let mut loader = Loader { loading: Vec::new(), loaded: Vec::new(), errors: Vec::new(), sources };
+    if let Some(file) = file {
+        loader.loading.push(canonical(file));
+    }
+    match loader.load(input, file) {
+        Some(m) if loader.errors.is_empty() => Ok(m),
+        _ => Err(loader.errors),
+    }
+}
+
+struct Loader<'a> {
+    /// The files whose imports are being loaded, to catch cycles.
+    loading: Vec<PathBuf>,
+    loaded: Vec<PathBuf>,
+    errors: Vec<FileErrors>,
+    sources: &'a mut Sources
+}
+impl Loader<'_> {
+    /// Parses `input` and loads its imports, returning `None` if it has
+    /// errors. Errors in imported files are collected along the way.
+    fn load(&mut self, input: &str, file: Option<&Path>) -> Option<Module> {
+        let show = |file: Option<&Path>| file.map(|f| f.display().to_string());
+        let mut m = match parse_file(input) {
+            Ok(m) => m,
+            Err(errors) => {
+                self.errors.push(FileErrors { file: show(file), src: input.to_string(), errors });
+                return None;
+            }
+        };
+        let at = self.sources.add(show(file).as_deref(), input);
+        m.shift(at);
+        let mut errors = Vec::new();
+        let dir = file.and_then(Path::parent).unwrap_or_else(|| Path::new(""));
+        let mut defs = Vec::new();
+        for imp in &m.imports {
+            let path = dir.join(&imp.path);
+            let key = canonical(&path);
+            if self.loading.contains(&key) {
+                errors.push(ParseError { typ: ImportCycle, pos: imp.span.start });
+                continue;
+            }
+            if self.loaded.contains(&key) {
+                continue;
+            }
+            let src = match fs::read_to_string(&path) {
+                Ok(src) => src,
+                Err(_) => {
+                    errors.push(ParseError { typ: NoImport, pos: imp.span.start });
+                    continue;
+                }
+            };
+            self.loading.push(key);
+            if let Some(sub) = self.load(&src, Some(&path)) {
+                defs.extend(sub.defs);
+            }
+            self.loaded.extend(self.loading.pop());
+        }
+        if !errors.is_empty() {
+            self.errors.push(FileErrors { file: show(file), src: input.to_string(), errors });
+            return None;
+        }
+        defs.append(&mut m.defs);
+        m.defs = defs;
+        Some(m)
+    }
+}
+/// The same file can be reached by different paths, like `a.lb` and
+/// `lib/../a.lb`.
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Checks whether a parse error is only because the input stopped early, so
+/// that more input could still complete it: an unclosed parenthesis or
+/// block comment, or a lambda, `let` or definition still waiting for its
+/// body or its `:=`.
+pub fn is_incomplete(err: &ParseError, input: &str) -> bool {
+    match err.typ {
+        NoCommentClose => true,
+        NoClose | EmptyCall | BadLet => err.pos >= input.len() || input[err.pos..].trim().is_empty(),
+        _ => false,
+    }
+}
+
+/// Parses an expression in the context `ctx`, carrying on after errors: each
+/// one is added to `errors`, and the part of the input that failed becomes a
+/// `SpanTree::Error` node. Stray `)`s, `;`s and other reserved symbols are
+/// skipped, and broken lambdas and `let`s are skipped up to the next `;` or
+/// `)`.
+fn get_parse(i: &mut usize, input: &[u8], ctx: PCtx, errors: &mut Vec<ParseError>) -> Parsed {
+    let errors_before = errors.len();
+    let mut ex: Option<Parsed> = None;
     let mut closed = false;
+    // the lines of a doc comment waiting for the `let` it documents
+    let mut doc = Vec::new();
     while *i < input.len() {
         match input[*i] {
+            b'#' if check_seq(i, input, b"##") => {
+                doc.push(get_doc_line(i, input));
+                continue;
+            }
             b'#' => {
-                *i += 1;
-                get_comment(i, input);
+                // the comment takes up the rest of the input, so nothing
+                // else can be wrong with it
+                if let Err(e) = get_comment(i, input) {
+                    errors.push(e);
+                    let start = *i;
+                    *i = input.len();
+                    return ex.unwrap_or_else(|| hole(start, *i));
+                }
             }
             b'(' => {
+                let start = *i;
                 *i += 1;
-                ex = push_call(ex, get_parse(i, input, PCtx::Paren)?);
+                let (inner, sp) = get_parse(i, input, PCtx::Paren, errors);
+                // the parentheses belong to the term they group
+                ex = push_call(ex, (inner, sp.with_span(Span::new(start, *i))));
             }
             b')' => {
                 match ctx {
@@ -93,72 +587,187 @@ fn get_parse(i: &mut usize, input: &[u8], ctx: PCtx) -> Result<Exp, ParseError>
                         *i += 1;
                         break;
                     }
-                    PCtx::Root | PCtx::Let => {
-                        return p_err(CloseEarly, *i);
+                    PCtx::Root | PCtx::Def => {
+                        errors.push(ParseError { typ: CloseEarly, pos: *i });
+                        *i += 1;
                     }
-                    PCtx::Fun => {
+                    PCtx::Fun | PCtx::Let => {
                         break;
                     }
                 }
             }
             b';' => match ctx {
-                PCtx::Let => {
+                PCtx::Def => {
                     *i += 1;
                     break;
                 }
                 PCtx::Root | PCtx::Paren => {
-                    return p_err(Reserved, *i);
+                    errors.push(ParseError { typ: Reserved, pos: *i });
+                    *i += 1;
                 }
                 _ => {
                     break;
                 }
             }
             b'\\' => {
+                let start = *i;
                 *i += 1;
-                ex = push_call(ex, get_fun(i, input)?);
+                ex = push_call(ex, get_fun(start, i, input, errors));
             }
             _ if check_seq(i, input, &[LAM_HI, LAM_LO]) => {
+                let start = *i;
                 *i += 2;
-                ex = push_call(ex, get_fun(i, input)?);
+                ex = push_call(ex, get_fun(start, i, input, errors));
             }
             _ if check_seq(i, input, b":=") => {
-                return p_err(Reserved, *i)
+                errors.push(ParseError { typ: Reserved, pos: *i });
+                *i += 2;
+            }
+            b'.' => {
+                errors.push(ParseError { typ: Reserved, pos: *i });
+                *i += 1;
             }
             _ if is_let(i, input) => {
+                let start = *i;
                 *i += 3;
-                let (name, val) = get_let(i, input)?;
-                let body = get_parse(i, input, PCtx::Fun)?;
-                ex = push_call(ex, Call(
-                    Box::new(Lamb(name, Box::new(body))),
-                    Box::new(val)
-                ));
+                ex = push_call(ex, get_let(start, i, input, join_doc(&mut doc), errors));
             }
             ch if is_space(ch) => {
                 *i += 1;
+                continue;
             }
             _ => {
-                 ex = push_call(ex, Var(get_var(i, input)));
+                let start = *i;
+                let name = get_var(i, input);
+                // larger numerals are deep enough to overflow the stack
+                if is_literal(&name) && name.parse::<u64>().map_or(true, |n| n > MAX_LITERAL) {
+                    errors.push(ParseError { typ: BigLiteral, pos: start });
+                    ex = push_call(ex, hole(start, *i));
+                } else {
+                    ex = push_call(ex, (Var(name), SpanTree::Var(Span::new(start, *i))));
+                }
             }
         }
+        // a doc comment only documents what comes right after it
+        doc.clear();
     }
     if ctx == PCtx::Paren && !closed {
-        p_err(NoClose, *i)
+        errors.push(ParseError { typ: NoClose, pos: *i });
+        ex.unwrap_or_else(|| hole(*i, *i))
     } else {
-        match ex {
-            Some(e) => Ok(e),
-            None => p_err(EmptyCall, if ctx == PCtx::Paren { *i-1 } else { *i })
+        ex.unwrap_or_else(|| {
+            let pos = if ctx == PCtx::Paren { *i-1 } else { *i };
+            // whatever was skipped over has been reported already
+            if errors.len() == errors_before {
+                errors.push(ParseError { typ: EmptyCall, pos });
+            }
+            hole(pos, pos)
+        })
+    }
+}
+
+/// The name standing in for a part of the input that failed to parse.
+pub const HOLE: &str = "?";
+
+/// Stands in for the part of the input from `start` to `end`, which failed
+/// to parse.
+fn hole(start: usize, end: usize) -> Parsed {
+    (Var(HOLE.to_string()), SpanTree::Error(Span::new(start, end)))
+}
+
+/// Skips ahead to the next `;` or `)` outside of any parentheses opened
+/// after `i`, or the end of the input, to carry on parsing after an error.
+fn resync(i: &mut usize, input: &[u8]) {
+    let mut depth = 0;
+    while *i < input.len() {
+        match input[*i] {
+            b'#' => {
+                *i = comment_end(i, input).unwrap_or(input.len());
+                continue;
+            }
+            b'(' => depth += 1,
+            b')' | b';' if depth == 0 => return,
+            b')' => depth -= 1,
+            _ => {}
+        }
+        *i += 1;
+    }
+}
+
+/// Finds the end of the comment starting at `i`: the line break ending a `#`
+/// comment, or just past the `|#` closing a `#| ... |#` block comment, which
+/// may be nested. `None` if a block comment is never closed.
+fn comment_end(i: &usize, input: &[u8]) -> Option<usize> {
+    let mut j = *i;
+    if !check_seq(&j, input, b"#|") {
+        while j < input.len() && input[j] != b'\n' {
+            j += 1;
+        }
+        return Some(j);
+    }
+    let mut depth = 0;
+    while j < input.len() {
+        if check_seq(&j, input, b"#|") {
+            depth += 1;
+            j += 2;
+        } else if check_seq(&j, input, b"|#") {
+            depth -= 1;
+            j += 2;
+            if depth == 0 {
+                return Some(j);
+            }
+        } else {
+            j += 1;
         }
     }
+    None
 }
 
-fn get_comment(i: &mut usize, input: &[u8]) {
+fn get_comment(i: &mut usize, input: &[u8]) -> Result<(), ParseError> {
+    match comment_end(i, input) {
+        Some(end) => {
+            *i = end;
+            Ok(())
+        }
+        None => p_err(NoCommentClose, *i)
+    }
+}
+
+/// Reads a line of a `##` doc comment, without the `##` and the space
+/// after it.
+fn get_doc_line(i: &mut usize, input: &[u8]) -> String {
+    let end = comment_end(i, input).unwrap_or(input.len());
+    let line = String::from_utf8_lossy(&input[*i+2..end]);
+    *i = end;
+    line.strip_prefix(' ').unwrap_or(&line).trim_end().to_string()
+}
+/// Takes the lines of a doc comment read so far, if there are any.
+fn join_doc(doc: &mut Vec<String>) -> Option<String> {
+    if doc.is_empty() {
+        None
+    } else {
+        Some(std::mem::take(doc).join("\n"))
+    }
+}
+/// Like `skip_blank`, but gives the doc comment right before what comes
+/// next, if there is one.
+fn skip_blank_doc(i: &mut usize, input: &[u8]) -> Option<String> {
+    let mut doc = Vec::new();
     while *i < input.len() {
-        if input[*i] == b'\n' {
+        if is_space(input[*i]) {
             *i += 1;
+        } else if check_seq(i, input, b"##") {
+            doc.push(get_doc_line(i, input));
+        } else if input[*i] == b'#' {
+            doc.clear();
+            if get_comment(i, input).is_err() {
+                break;
+            }
+        } else {
             break;
         }
-        *i += 1;
     }
+    join_doc(&mut doc)
 }
 
 fn get_var(i: &mut usize, input: &[u8]) -> String {
@@ -171,8 +780,9 @@ fn get_var(i: &mut usize, input: &[u8]) -> String {
     String::from_utf8(s).unwrap()
 }
 
-fn get_fun(i: &mut usize, input: &[u8]) -> Result<Exp, ParseError> {
-    let mut args: Vec<String> = Vec::with_capacity(5);
+/// Parses a lambda after its `\` or `λ`, which started at `start`.
+fn get_fun(start: usize, i: &mut usize, input: &[u8], errors: &mut Vec<ParseError>) -> Parsed {
+    let mut args: Vec<(String, usize)> = Vec::with_capacity(5);
     while *i < input.len() {
         match input[*i] {
             b'.' => {
@@ -183,41 +793,82 @@ fn get_fun(i: &mut usize, input: &[u8]) -> Result<Exp, ParseError> {
                 *i += 1;
             }
             _ if is_reserved(i, input) => {
-                return p_err(BadArgs, *i);
+                errors.push(ParseError { typ: BadArgs, pos: *i });
+                resync(i, input);
+                return hole(start, *i);
             }
             _ => {
-                args.push(get_var(i, input));
+                let pos = *i;
+                args.push((get_var(i, input), pos));
             }
         }
     }
-    
+
     if args.is_empty() {
-        p_err(EmptyArgs, *i-1)
-    } else if *i > input.len() {
-        p_err(BadArgs, *i)
-    } else {
-        let bod = get_parse(i, input, PCtx::Fun)?;
-        Ok(args.into_iter().rev().fold(bod, |r, a| Lamb(a, Box::new(r))))
+        errors.push(ParseError { typ: EmptyArgs, pos: *i-1 });
+        resync(i, input);
+        return hole(start, *i);
     }
+    let (bod, sp) = get_parse(i, input, PCtx::Fun, errors);
+    let end = sp.span().end;
+    let (ex, sp) = args.into_iter().rev().fold((bod, sp), |(r, sp), (a, pos)| {
+        (Lamb(a, Box::new(r)), SpanTree::Lamb(Span::new(pos, end), Box::new(sp)))
+    });
+    // the outermost lambda also covers the `\`
+    (ex, sp.with_span(Span::new(start, end)))
 }
 
-fn get_let(i: &mut usize, input: &[u8]) -> Result<(String, Exp), ParseError> {
+/// Parses a `let` after its keyword, which started at `start`, along with
+/// its body. `doc` is the doc comment before it, if any.
+fn get_let(start: usize, i: &mut usize, input: &[u8], doc: Option<String>, errors: &mut Vec<ParseError>) -> Parsed {
     skip_space(i, input);
     let name = get_var(i, input);
     skip_space(i, input);
-    if check_seq(i, input, b":=") {
+    let val = if check_seq(i, input, b":=") {
         *i += 2;
+        skip_space(i, input);
+        get_parse(i, input, PCtx::Let, errors)
     } else {
-        return p_err(BadLet, *i)
+        errors.push(ParseError { typ: BadLet, pos: *i });
+        let bad = *i;
+        resync(i, input);
+        if input.get(*i) != Some(&b';') {
+            return hole(start, *i);
+        }
+        hole(bad, *i)
+    };
+    match input.get(*i) {
+        Some(b';') => *i += 1,
+        // a `)` before the `;` leaves the `let` without a body
+        Some(_) => {
+            errors.push(ParseError { typ: CloseEarly, pos: *i });
+            return hole(start, *i);
+        }
+        // the body is reported missing once there's no more input
+        None => {}
     }
-    skip_space(i, input);
-    let val = get_parse(i, input, PCtx::Let)?;
-    Ok((name, val))
+    let body = get_parse(i, input, PCtx::Fun, errors);
+    let span = Span::new(start, body.1.span().end);
+    (
+        Call(Box::new(Lamb(name, Box::new(body.0))), Box::new(val.0)),
+        SpanTree::Let(span, Box::new(body.1), Box::new(val.1), doc)
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::reduce::Reduc;
+
+    /// What parsing `src` gives, as if it were found `at` bytes into a file.
+    fn parsed(src: &str, at: usize) -> Parsed {
+        let (ex, spans) = parse_spanned(src).unwrap();
+        (ex, spans.shift(at))
+    }
+    fn def(name: &str, src: &str, at: usize) -> Def {
+        let (val, spans) = parsed(src, at);
+        Def { name: name.to_string(), val, spans, doc: None }
+    }
 
     #[test]
     fn basic_parse() {
@@ -296,6 +947,13 @@ mod tests {
             Ok(Lamb("filets".to_string(), Box::new(Var("filets".to_string())))));
     }
     #[test]
+    fn big_literals() {
+        assert!(parse("f 1000").is_ok());
+        assert_eq!(parse("f 1001"), p_err(BigLiteral, 2));
+        assert_eq!(parse("(100000000000000000000000)"), p_err(BigLiteral, 1));
+        assert!(parse("f x10001").is_ok());
+    }
+    #[test]
     fn reserved() {
         assert_eq!(parse(":="), p_err(Reserved, 0));
         assert_eq!(parse("x := y"), p_err(Reserved, 2));
@@ -331,12 +989,318 @@ mod tests {
         assert_eq!(parse("("), p_err(NoClose, 1));
         assert_eq!(parse("(x y z"), p_err(NoClose, 6));
         assert_eq!(parse("(x (y z)"), p_err(NoClose, 8));
+        assert_eq!(parse("a. b"), p_err(Reserved, 1));
+        assert_eq!(parse("\\x. x. y"), p_err(Reserved, 5));
     }
     #[test]
     fn err_close_early() {
         assert_eq!(parse(")"), p_err(CloseEarly, 0));
         assert_eq!(parse("x y) z"), p_err(CloseEarly, 3));
         assert_eq!(parse("(x y) z)"), p_err(CloseEarly, 7));
+        // a term left empty by what was skipped isn't reported again
+        assert_eq!(parse_partial(")").1, vec![ParseError { typ: CloseEarly, pos: 0 }]);
+        assert_eq!(parse_partial("(.)").1, vec![ParseError { typ: Reserved, pos: 1 }]);
+        assert_eq!(parse_file(")").unwrap_err(), vec![ParseError { typ: CloseEarly, pos: 0 }]);
+    }
+    #[test]
+    fn modules() {
+        let m = parse_file("\
+I := \\x. x\n\
+K := \\x y. x\n\
+K I").unwrap();
+        assert_eq!(m.defs, vec![
+            def("I", "\\x. x", 5),
+            def("K", "\\x y. x", 16),
+        ]);
+        assert_eq!(m.main, parse("K I").ok());
+        assert_eq!(m.inline(), parse("(\\x y. x) (\\x. x)").ok());
+    }
+    #[test]
+    fn modules_layout() {
+        // definitions may span lines, end at `;`, and be separated by comments
+        let m = parse_file(concat!(
+            "# combinators\n",
+            "S := \\x y z.\n",
+            "    x z (y z)   # substitution\n",
+            "\n",
+            "K := \\x y. x; I := S K K # trailing comment\n",
+        )).unwrap();
+        assert_eq!(m.defs.len(), 3);
+        assert_eq!(m.defs[0].val, parse("\\x y z. x z (y z)").unwrap());
+        assert_eq!(m.defs[2].val, parse("S K K").unwrap());
+        assert_eq!(m.main, None);
+        assert_eq!(m.inline(), None);
+        assert_eq!(parse_file("x y").unwrap(), Module {
+            imports: vec![],
+            defs: vec![],
+            main: parse("x y").ok(),
+            main_spans: Some(parsed("x y", 0).1),
+        });
+        assert_eq!(parse_file("a := x # first\nb := y # second\n").unwrap().defs.len(), 2);
+    }
+    #[test]
+    fn modules_inline() {
+        let m = parse_file("a := b\nb := \\x. x\nf := \\b. a b\nf c").unwrap();
+        assert_eq!(m.inline(), parse("(\\b'. b b') c").ok());
+        let m = parse_file("a := x\na := a a\na").unwrap();
+        assert_eq!(m.inline(), parse("x x").ok());
+    }
+    #[test]
+    fn err_modules() {
+        let err = |inp| parse_file(inp).unwrap_err();
+        assert_eq!(err("a := (x\nb := y"), vec![ParseError { typ: NoClose, pos: 7 }]);
+        assert_eq!(err("a := x)\n"), vec![ParseError { typ: CloseEarly, pos: 6 }]);
+        assert_eq!(err("a :=\nb := y"), vec![ParseError { typ: EmptyCall, pos: 4 }]);
+        assert_eq!(err("x\na := y"), vec![ParseError { typ: DefAfterMain, pos: 2 }]);
+        assert_eq!(parse_file("a := x y\nz\n"), Ok(Module {
+            imports: vec![],
+            defs: vec![def("a", "x y", 5)],
+            main: parse("z").ok(),
+            main_spans: Some(parsed("z", 9).1),
+        }));
+    }
+    #[test]
+    fn incomplete() {
+        for inp in &["(a b", "\\x y.", "λx.\n", "let x := y;", "let x := y", "a := # body next\n", "f (\\x. (x",
+                "let \n", "let x", "let x\n", "let x :=", "I := \\x. x\nlet x\n"] {
+            let e = parse_file(inp).unwrap_err();
+            assert!(e.iter().all(|e| is_incomplete(e, inp)), "{:?} from {:?}", e, inp);
+        }
+        for inp in &["a b)", "()", "\\. x", "a :=\nb := y", "a; b", "let x y := z; x", "a := (x\nb := y"] {
+            let e = parse_file(inp).unwrap_err();
+            assert!(!e.iter().all(|e| is_incomplete(e, inp)), "{:?} from {:?}", e, inp);
+        }
     }
+    #[test]
+    fn spans() -> Result<(), ParseError> {
+        let src = "(\\x y. x) a (let b := c; b)";
+        let (ex, spans) = parse_spanned(src)?;
+        assert!(spans.fits(&ex));
+        let text = |red: Reduc| spans.at(&red).map(|s| &src[s.start..s.end]);
+        assert_eq!(text(Reduc::Beta), Some(src));
+        assert_eq!(text(Reduc::Left(Box::new(Reduc::Beta))), Some("(\\x y. x) a"));
+        assert_eq!(text(Reduc::Left(Box::new(Reduc::Left(Box::new(Reduc::Eta))))), Some("(\\x y. x)"));
+        assert_eq!(text(Reduc::Left(Box::new(Reduc::Left(Box::new(Reduc::Body(Box::new(Reduc::Eta))))))), Some("y. x"));
+        assert_eq!(text(Reduc::Right(Box::new(Reduc::Beta))), Some("(let b := c; b)"));
+        assert_eq!(text(Reduc::Right(Box::new(Reduc::Right(Box::new(Reduc::Eta))))), None);
+        assert_eq!(spans.span().show(src).to_string(), "1:1-1:28");
+        let (_, spans) = parse_spanned("λx.\n  f x")?;
+        assert_eq!(spans.span().show("λx.\n  f x").to_string(), "1:1-2:6");
+        Ok(())
+    }
+    #[test]
+    fn reports() {
+        let src = "a := x\nlet y = z; y";
+        let e = &parse_file(src).unwrap_err()[0];
+        assert_eq!(e.report(src, Some("test.lb")), concat!(
+            "error: missing `:=` after let name\n",
+            " --> test.lb:2:7\n",
+            "  |\n",
+            "2 | let y = z; y\n",
+            "  |       ^\n",
+            "  = hint: write `let name := value; body`\n",
+        ));
+        let src = "(λx. x; y\n\n";
+        let e = parse(src).unwrap_err();
+        assert_eq!(e.report(src, None), concat!(
+            "error: unexpected reserved symbol\n",
+            " --> 1:7\n",
+            "  |\n",
+            "1 | (λx. x; y\n",
+            "  |       ^\n",
+            "  = hint: `;` is only allowed after a let binding or a definition\n",
+        ));
+        let src = "f (x\n";
+        let e = parse(src).unwrap_err();
+        assert_eq!(e.report(src, None), concat!(
+            "error: expected `)`\n",
+            " --> 1:5\n",
+            "  |\n",
+            "1 | f (x\n",
+            "  |     ^\n",
+            "  = hint: some `(` is never closed\n",
+        ));
+    }
+    #[test]
+    fn recovery() {
+        let src = concat!(
+            "a := (x\n",
+            "b := y\n",
+            "c := let z; z\n",
+            "  w\n",
+            "a b\n",
+            "d := \\. y\n",
+            "b c\n",
+        );
+        let items = parse_items(src);
+        assert_eq!(items, vec![
+            Item::Error(ParseError { typ: NoClose, pos: 7 }),
+            Item::Def(def("b", "y", 13)),
+            Item::Error(ParseError { typ: BadLet, pos: 25 }),
+            Item::Main(parsed("a b", 33)),
+            Item::Error(ParseError { typ: DefAfterMain, pos: 37 }),
+            Item::Error(ParseError { typ: EmptyArgs, pos: 43 }),
+            Item::Main(parsed("b c", 47)),
+        ]);
+        assert_eq!(Module::from_items(items).unwrap_err().len(), 4);
+        let m = Module::from_items(parse_items("a := x\n\nb := y\na b\n  c")).unwrap();
+        assert_eq!(m.defs.len(), 2);
+        assert_eq!(m.main, parse("a b c").ok());
+    }
+    #[test]
+    fn recovery_in_expressions() -> Result<(), ParseError> {
+        let src = "f (\\. x) (let y = z; y) ;) 99999 (g";
+        let ((ex, spans), errors) = parse_partial(src);
+        let err = |typ, pos| ParseError { typ, pos };
+        assert_eq!(errors, vec![err(EmptyArgs, 4), err(BadLet, 16), err(Reserved, 24), err(CloseEarly, 25),
+            err(BigLiteral, 27), err(NoClose, 35)]);
+        // what failed is left out, and what didn't is still there
+        assert_eq!(ex, parse("f ? ((\\y. y) ?) ? g")?);
+        assert!(spans.fits(&ex));
+        fn holes<'a>(sp: &SpanTree, src: &'a str, out: &mut Vec<&'a str>) {
+            match sp {
+                SpanTree::Error(s) => out.push(&src[s.start..s.end]),
+                SpanTree::Var(_) => {}
+                SpanTree::Call(_, a, b) | SpanTree::Let(_, a, b, _) => {
+                    holes(a, src, out);
+                    holes(b, src, out);
+                }
+                SpanTree::Lamb(_, r) => holes(r, src, out),
+            }
+        }
+        let mut found = Vec::new();
+        holes(&spans, src, &mut found);
+        assert_eq!(found, vec!["(\\. x)", "= z", "99999"]);
+        // a `let` cut short by a `)` is left out entirely
+        let ((ex, _), errors) = parse_partial("(let x := y) (let z) w");
+        assert_eq!(errors, vec![err(CloseEarly, 11), err(BadLet, 19)]);
+        assert_eq!(ex, parse("? ? w")?);
+        assert_eq!(parse_file("a := f (\\x\\. x) (y;\nb := c\n").unwrap_err(),
+            vec![err(BadArgs, 10), err(Reserved, 18), err(NoClose, 19)]);
+        Ok(())
+    }
+    #[test]
+    fn block_comments() {
+        assert_eq!(parse("a #| b |# c"), parse("a c"));
+        assert_eq!(parse("a #| b #| c |# d |# e"), parse("a e"));
+        assert_eq!(parse("#|#|# |#|# a #||#"), parse("a"));
+        assert_eq!(parse("a #| b #| c |# d"), p_err(NoCommentClose, 2));
+        let m = parse_file(concat!(
+            "a := x\n",
+            "#|\n",
+            "b := (y\n",
+            "|#\n",
+            "c := z # (\n",
+            "a c\n",
+        )).unwrap();
+        assert_eq!(m.defs.len(), 2);
+        assert_eq!(m.inline(), parse("x z").ok());
+        let e = parse_file("a := x\n#| b := y\n").unwrap_err();
+        assert_eq!(e, vec![ParseError { typ: NoCommentClose, pos: 7 }]);
+        assert!(is_incomplete(&e[0], "a := x\n#| b := y\n"));
+    }
+    #[test]
+    fn docs() {
+        let m = parse_file(concat!(
+            "## The identity.\n",
+            "I := \\x. x\n",
+            "K := \\x y. x\n",
+            "##  Applies a function\n",
+            "## to itself.\n",
+            "M := \\x. x x\n",
+            "## stray\n",
+            "# not a doc\n",
+            "S := \\x y z. x z (y z)\n",
+            "## Two.\n",
+            "let two := \\f x. f (f x);\n",
+            "## not here\n",
+            "two M (let y := ## nor here\n a; y)\n",
+        )).unwrap();
+        let docs: Vec<Option<&str>> = m.defs.iter().map(|d| d.doc.as_deref()).collect();
+        assert_eq!(docs, vec![Some("The identity."), None, Some(" Applies a function\nto itself."), None]);
+        fn let_docs(sp: &SpanTree, out: &mut Vec<Option<String>>) {
+            match sp {
+                SpanTree::Let(_, body, val, doc) => {
+                    out.push(doc.clone());
+                    let_docs(val, out);
+                    let_docs(body, out);
+                }
+                SpanTree::Call(_, a, b) => {
+                    let_docs(a, out);
+                    let_docs(b, out);
+                }
+                SpanTree::Lamb(_, r) => let_docs(r, out),
+                SpanTree::Var(_) | SpanTree::Error(_) => {}
+            }
+        }
+        let mut found = Vec::new();
+        let_docs(&m.main_spans.unwrap(), &mut found);
+        assert_eq!(found, vec![Some("Two.".to_string()), None]);
+        // lets in definitions and parentheses have them too
+        let (_, spans) = parse_spanned("f (## Doc.\nlet x := y; x)").unwrap();
+        let mut found = Vec::new();
+        let_docs(&spans, &mut found);
+        assert_eq!(found, vec![Some("Doc.".to_string())]);
+        let d = &parse_file("a := ## Doc.\n  let x := y; x\n").unwrap().defs[0];
+        assert_eq!(d.doc, None);
+        let mut found = Vec::new();
+        let_docs(&d.spans, &mut found);
+        assert_eq!(found, vec![Some("Doc.".to_string())]);
+    }
+    #[test]
+    fn imports() {
+        let m = parse_file("import \"a.lb\"\nimport \"lib/b c.lb\"; # comment\nx := y\nimport \"d.lb\"; x\n").unwrap();
+        let paths: Vec<&str> = m.imports.iter().map(|imp| imp.path.as_str()).collect();
+        assert_eq!(paths, vec!["a.lb", "lib/b c.lb", "d.lb"]);
+        assert_eq!(m.imports[0].span, Span::new(7, 13));
+        assert_eq!(m.main, parse("x").ok());
+        // only a quoted name makes an import
+        assert_eq!(parse_file("import x").unwrap().main, parse("import x").ok());
+        assert_eq!(parse_items("import \"a\nb := x\n"), vec![
+            Item::Error(ParseError { typ: NoQuoteClose, pos: 7 }),
+            Item::Def(def("b", "x", 15)),
+        ]);
+        assert_eq!(parse_items("import \"a\" x\nx\nimport \"b\"\n"), vec![
+            Item::Error(ParseError { typ: Incomplete, pos: 11 }),
+            Item::Main(parsed("x", 13)),
+            Item::Error(ParseError { typ: DefAfterMain, pos: 15 }),
+            Item::Import(Import { path: "b".to_string(), span: Span::new(22, 25) }),
+        ]);
+    }
+    #[test]
+    fn load_imports() {
+        let dir = std::env::temp_dir().join(format!("lambda-imports-{}", std::process::id()));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::write(dir.join("lib/a.lb"), "import \"b.lb\"\n## A.\na := b b\n").unwrap();
+        fs::write(dir.join("lib/b.lb"), "b := x\nb\n").unwrap();
+        fs::write(dir.join("cycle.lb"), "import \"lib/c.lb\"\n").unwrap();
+        fs::write(dir.join("lib/c.lb"), "import \"../cycle.lb\"\nimport \"a.lb\"\nimport \"bad.lb\"\nimport \"none.lb\"\n").unwrap();
+        fs::write(dir.join("lib/bad.lb"), "bad := (\n").unwrap();
+
+        let main = dir.join("main.lb");
+        let mut sources = Sources::default();
+        let m = load_file("import \"lib/a.lb\"\nimport \"lib/b.lb\"\nb := a\nb", Some(&main), &mut sources).unwrap();
+        let names: Vec<&str> = m.defs.iter().map(|d| d.name.as_str()).collect();
+        // b.lb is only loaded once, and its main expression is ignored
+        assert_eq!(names, vec!["b", "a", "b"]);
+        assert_eq!(m.inline(), parse("x x").ok());
+        assert_eq!(m.defs[1].doc.as_deref(), Some("A."));
+        // spans say which file each part came from
+        let a = &m.defs[1].spans;
+        assert_eq!(sources.show(a.span()), format!("{}:3:6-3:9", dir.join("lib/a.lb").display()));
+        assert_eq!(sources.text(a.span()), "b b");
+        let b = m.main_spans.unwrap();
+        assert_eq!(sources.show(b.span()), format!("{}:4:1-4:2", main.display()));
 
-}
\ No newline at end of file
+        let errors = load_file("import \"lib/c.lb\"\n", Some(&dir.join("cycle.lb")), &mut sources).unwrap_err();
+        let found: Vec<(String, Vec<PErrType>)> = errors.into_iter()
+            .map(|e| (e.file.unwrap(), e.errors.into_iter().map(|e| e.typ).collect()))
+            .collect();
+        let bad = dir.join("lib/bad.lb").display().to_string();
+        assert_eq!(found, vec![
+            (bad, vec![NoClose]),
+            (dir.join("lib/c.lb").display().to_string(), vec![ImportCycle, NoImport]),
+        ]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
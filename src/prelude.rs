@@ -0,0 +1,135 @@
+/// Definitions loaded into every session unless `--no-prelude` is given.
+pub const PRELUDE: &str = include_str!("prelude.lb");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::{ Exp, assert_alpha_eq };
+    use crate::parser::{ Def, parse, parse_file, inline, ParseError };
+    use crate::numeral::{ Encoding, MAX_LITERAL, desugar };
+    use crate::decode::{ Decode, decode };
+    use crate::reduce::{ Limits, NameStyle, reduce_full, strat_norm };
+
+    /// The normal form of `src` with the prelude's definitions substituted.
+    fn eval(src: &str) -> Result<Exp, ParseError> {
+        let defs: Vec<Def> = parse_file(PRELUDE).expect("prelude doesn't parse").defs.into_iter()
+            .map(|d| Def { val: desugar(d.val, Encoding::Church), ..d })
+            .collect();
+        let ex = inline(desugar(parse(src)?, Encoding::Church), &defs, NameStyle::Prime);
+        let limits = Limits { steps: Some(100_000), ..Limits::default() };
+        match reduce_full(strat_norm, ex, limits, NameStyle::Prime) {
+            Ok((ex, _)) => Ok(ex),
+            Err(e) => panic!("{} didn't reach a normal form: {:?}", src, e.budget),
+        }
+    }
+
+    fn check(pairs: &[(&str, &str)]) -> Result<(), ParseError> {
+        for (a, b) in pairs {
+            assert_alpha_eq!(eval(a)?, eval(b)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn parses() {
+        let m = parse_file(PRELUDE).expect("prelude doesn't parse");
+        assert!(m.main.is_none());
+        assert!(m.defs.iter().any(|d| d.name == "Y" && d.doc.is_some()));
+    }
+
+    #[test]
+    fn combinators() -> Result<(), ParseError> {
+        check(&[
+            ("S K K x", "x"),
+            ("S K S x", "I x"),
+            ("B f g x", "f (g x)"),
+            ("C f x y", "f y x"),
+            ("W f x", "f x x"),
+            ("M I", "I"),
+        ])
+    }
+
+    #[test]
+    fn fixed_points() -> Result<(), ParseError> {
+        let fac = "(\\r n. 0? n 1 (* n (r (pred n))))";
+        check(&[
+            (&format!("Y {} 4", fac), "24"),
+            (&format!("Z {} 4", fac), "24"),
+            (&format!("Theta {} 4", fac), "24"),
+        ])
+    }
+
+    #[test]
+    fn booleans_and_pairs() -> Result<(), ParseError> {
+        check(&[
+            ("not true", "false"),
+            ("and true false", "false"),
+            ("or false true", "true"),
+            ("xor true true", "false"),
+            ("xor true false", "true"),
+            ("if false a b", "b"),
+            ("fst (pair a b)", "a"),
+            ("snd (pair a b)", "b"),
+        ])
+    }
+
+    #[test]
+    fn arithmetic() -> Result<(), ParseError> {
+        check(&[
+            ("succ 2", "3"),
+            ("pred 3", "2"),
+            ("pred 0", "0"),
+            ("+ 2 3", "5"),
+            ("* 2 3", "6"),
+            ("^ 2 3", "8"),
+            ("- 5 2", "3"),
+            ("- 2 5", "0"),
+            ("0? 0", "true"),
+            ("0? 1", "false"),
+            ("<= 2 3", "true"),
+            ("<= 3 2", "false"),
+            (">= 3 3", "true"),
+            ("< 3 3", "false"),
+            ("> 4 3", "true"),
+            ("== 3 3", "true"),
+            ("== 2 3", "false"),
+            ("/ 7 2", "3"),
+            ("/ 6 3", "2"),
+            ("% 7 3", "1"),
+            ("% 6 3", "0"),
+        ])
+    }
+
+    #[test]
+    fn lists() -> Result<(), ParseError> {
+        let xs = "(cons 1 (cons 2 (cons 3 nil)))";
+        check(&[
+            (&format!("head {}", xs), "1"),
+            (&format!("tail {}", xs), "cons 2 (cons 3 nil)"),
+            ("tail nil", "nil"),
+            (&format!("null? {}", xs), "false"),
+            ("null? nil", "true"),
+            (&format!("map succ {}", xs), "cons 2 (cons 3 (cons 4 nil))"),
+            (&format!("foldr - 0 {}", xs), "- 1 (- 2 (- 3 0))"),
+            (&format!("foldl - 9 {}", xs), "3"),
+            (&format!("filter (< 1) {}", xs), "cons 2 (cons 3 nil)"),
+            (&format!("append {} (cons 4 nil)", xs), "cons 1 (cons 2 (cons 3 (cons 4 nil)))"),
+            (&format!("length {}", xs), "3"),
+            (&format!("sum {}", xs), "6"),
+            (&format!("reverse {}", xs), "cons 3 (cons 2 (cons 1 nil))"),
+        ])
+    }
+
+    #[test]
+    fn largest_literals() {
+        // on a stack the size of the main thread's, which is where the
+        // program runs
+        let sum = std::thread::Builder::new().stack_size(8 << 20).spawn(|| {
+            let ex = eval(&format!("+ {0} {0}", MAX_LITERAL)).unwrap();
+            (ex.size(), ex.to_string().len(), decode(&ex, Decode::all(), Encoding::Church).to_string())
+        }).unwrap().join().expect("overflowed the stack");
+        assert_eq!(sum.0, 2 * MAX_LITERAL as usize * 2 + 3);
+        assert!(sum.1 > 2 * MAX_LITERAL as usize);
+        assert_eq!(sum.2, (2 * MAX_LITERAL).to_string());
+    }
+}
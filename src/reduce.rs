@@ -3,6 +3,7 @@ use Exp::*;
 
 use std::fmt;
 use std::iter::Iterator;
+use std::sync::atomic::{ AtomicBool, Ordering };
 // use std::mem::swap;
 
 #[derive(PartialEq)]
@@ -11,7 +12,7 @@ pub enum Reduc {
     Right(Box<Reduc>),
     Body(Box<Reduc>),
     Beta,
-    // Eta,
+    Eta,
     Irred
 }
 impl fmt::Display for Reduc {
@@ -21,7 +22,7 @@ impl fmt::Display for Reduc {
             Reduc::Right(r) => write!(f, "(_ {})", r),
             Reduc::Body(r) => write!(f, "(\\. {})", r),
             Reduc::Beta => write!(f, "β"),
-            // Reduc::Eta => write!(f, "η"),
+            Reduc::Eta => write!(f, "η"),
             Reduc::Irred => write!(f, "-"),
         }
     }
@@ -32,20 +33,24 @@ impl fmt::Debug for Reduc {
     }
 }
 
-fn reduce_with(ex: Exp, red: &Reduc) -> Exp {
+fn reduce_with(ex: Exp, red: &Reduc, names: NameStyle) -> Exp {
     match (ex, red) {
         (Call(a, b), Reduc::Beta) => match *a {
-            Lamb(x, r) => sub(*r, &x, &b),
+            Lamb(x, r) => sub(*r, &x, &b, names),
             a => panic!("bad beta reduction: lhs {}", a)
         }
         (Call(a, b), red) => match red {
-            Reduc::Left(red) => Call(Box::new(reduce_with(*a, red)), b),
-            Reduc::Right(red) => Call(a, Box::new(reduce_with(*b, red))),
+            Reduc::Left(red) => Call(Box::new(reduce_with(*a, red, names)), b),
+            Reduc::Right(red) => Call(a, Box::new(reduce_with(*b, red, names))),
             Reduc::Irred => Call(a, b),
             red => panic!("bad reduction: {} on {}", red, Call(a, b))
         }
+        (Lamb(x, r), Reduc::Eta) => match *r {
+            Call(a, b) if is_eta(&x, &a, &b) => *a,
+            r => panic!("bad eta reduction: {}", Lamb(x, Box::new(r)))
+        }
         (Lamb(x, r), red) => match red {
-            Reduc::Body(red) => Lamb(x, Box::new(reduce_with(*r, red))),
+            Reduc::Body(red) => Lamb(x, Box::new(reduce_with(*r, red, names))),
             Reduc::Irred => Lamb(x, r),
             red => panic!("bad reduction: {} on {}", red, Lamb(x, r))
         }
@@ -54,46 +59,163 @@ fn reduce_with(ex: Exp, red: &Reduc) -> Exp {
     }
 }
 
-pub fn reduce_step(strat: Strategy, ex: Exp) -> (Reduc, Exp) {
+/// A term representation that the reduction strategies can walk.
+pub trait Term: Clone {
+    fn view(&self) -> View<'_, Self>;
+    /// Checks whether the term is an eta redex, `\x. f x` with `x` not free in `f`.
+    fn is_eta(&self) -> bool;
+    /// How the representation renames bound variables that would be captured,
+    /// `()` if it never has to.
+    type Names: Copy + Default;
+    /// Contracts the redex `red` points to, renaming bound variables as
+    /// `names` says where needed.
+    fn reduce_with(self, red: &Reduc, names: Self::Names) -> Self;
+    fn size(&self) -> usize;
+}
+
+/// The shape of one node of a term.
+pub enum View<'a, T> {
+    Var,
+    Call(&'a T, &'a T),
+    Lamb(&'a T),
+}
+
+impl Term for Exp {
+    type Names = NameStyle;
+    fn view(&self) -> View<'_, Exp> {
+        match self {
+            Var(_) => View::Var,
+            Call(a, b) => View::Call(a, b),
+            Lamb(_, r) => View::Lamb(r),
+        }
+    }
+    fn is_eta(&self) -> bool {
+        match self {
+            Lamb(x, r) => match **r {
+                Call(ref a, ref b) => is_eta(x, a, b),
+                _ => false
+            }
+            _ => false
+        }
+    }
+    fn reduce_with(self, red: &Reduc, names: NameStyle) -> Exp {
+        reduce_with(self, red, names)
+    }
+    fn size(&self) -> usize {
+        Exp::size(self)
+    }
+}
+
+pub fn reduce_step<T: Term>(strat: Strategy<T>, ex: T, names: T::Names) -> (Reduc, T) {
     let red = strat(&ex);
-    let ex = reduce_with(ex, &red);
+    let ex = ex.reduce_with(&red, names);
     (red, ex)
 }
 
-pub fn reduce_full(strat: Strategy, ex: Exp) -> Exp {
-    let mut red: Reduc;
+/// Bounds on how much work a reduction may do before giving up.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    pub steps: Option<usize>,
+    pub size: Option<usize>,
+    /// A flag that aborts the reduction when set, e.g. from a signal handler.
+    pub interrupt: Option<&'static AtomicBool>,
+}
+impl Limits {
+    fn check<T: Term>(&self, steps: usize, ex: &T) -> Option<Budget> {
+        if self.interrupt.is_some_and(|flag| flag.load(Ordering::SeqCst)) {
+            Some(Budget::Interrupt)
+        } else if self.steps.is_some_and(|max| steps >= max) {
+            Some(Budget::Steps)
+        } else if self.size.is_some_and(|max| ex.size() > max) {
+            Some(Budget::Size)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Budget {
+    Steps, Size, Interrupt
+}
+
+/// A reduction that was abandoned after running out of budget, along with
+/// the term reached so far.
+#[derive(Debug, PartialEq)]
+pub struct Exhausted<T = Exp> {
+    pub budget: Budget,
+    pub steps: usize,
+    pub ex: T
+}
+
+/// Reduces `ex` until `strat` finds no more redexes, or gives up once any
+/// of `limits` is exceeded. Also returns the number of steps taken.
+pub fn reduce_full<T: Term>(strat: Strategy<T>, ex: T, limits: Limits, names: T::Names)
+    -> Result<(T, usize), Exhausted<T>> {
+    let mut steps = 0;
     let mut ex = ex;
     loop {
-        let t = reduce_step(strat, ex);
-        red = t.0;
-        ex = t.1;
+        let red = strat(&ex);
         if red == Reduc::Irred {
-            return ex;
+            return Ok((ex, steps));
         }
+        if let Some(budget) = limits.check(steps, &ex) {
+            return Err(Exhausted { budget, steps, ex });
+        }
+        ex = ex.reduce_with(&red, names);
+        steps += 1;
     }
 }
 
-pub struct ReducIter {
-    strat: Strategy,
-    ex: Exp
+pub struct ReducIter<T: Term = Exp> {
+    strat: Strategy<T>,
+    ex: T,
+    limits: Limits,
+    names: T::Names,
+    steps: usize,
+    exhausted: Option<Budget>
 }
-impl Iterator for ReducIter {
-    type Item = (Reduc, Exp);
-    fn next(&mut self) -> Option<(Reduc, Exp)> {
+impl<T: Term> ReducIter<T> {
+    pub fn limit(self, limits: Limits) -> ReducIter<T> {
+        ReducIter { limits, ..self }
+    }
+    pub fn names(self, names: T::Names) -> ReducIter<T> {
+        ReducIter { names, ..self }
+    }
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+    /// Which budget ran out, if the iterator stopped before reaching an
+    /// irreducible term.
+    pub fn exhausted(&self) -> Option<Budget> {
+        self.exhausted
+    }
+}
+impl<T: Term> Iterator for ReducIter<T> {
+    type Item = (Reduc, T);
+    fn next(&mut self) -> Option<(Reduc, T)> {
+        if self.exhausted.is_some() {
+            return None;
+        }
         let red = (self.strat)(&self.ex);
-        self.ex = reduce_with(self.ex.clone(), &red);
-        match red {
-            Reduc::Irred => None,
-            red => Some((red, (&self.ex).clone()))
+        if red == Reduc::Irred {
+            return None;
+        }
+        if let Some(budget) = self.limits.check(self.steps, &self.ex) {
+            self.exhausted = Some(budget);
+            return None;
         }
+        self.ex = self.ex.clone().reduce_with(&red, self.names);
+        self.steps += 1;
+        Some((red, self.ex.clone()))
     }
 }
 
-pub fn reduce_iter(strat: Strategy, ex: Exp) -> ReducIter {
-    ReducIter { strat, ex }
+pub fn reduce_iter<T: Term>(strat: Strategy<T>, ex: T) -> ReducIter<T> {
+    ReducIter { strat, ex, limits: Limits::default(), names: T::Names::default(), steps: 0, exhausted: None }
 }
 
-pub type Strategy = fn(&Exp) -> Reduc;
+pub type Strategy<T = Exp> = fn(&T) -> Reduc;
 
 fn wrap_red(wrap: fn(Box<Reduc>) -> Reduc, red: Reduc) -> Reduc {
     match red {
@@ -102,10 +224,10 @@ fn wrap_red(wrap: fn(Box<Reduc>) -> Reduc, red: Reduc) -> Reduc {
     }
 }
 
-pub fn strat_byname(ex: &Exp) -> Reduc {
-    match ex {
-        Call(a, b) => match **a {
-            Lamb(_, _) => Reduc::Beta,
+pub fn strat_byname<T: Term>(ex: &T) -> Reduc {
+    match ex.view() {
+        View::Call(a, b) => match a.view() {
+            View::Lamb(_) => Reduc::Beta,
             _ => match strat_byname(a) {
                 Reduc::Irred => wrap_red(Reduc::Right, strat_byname(b)),
                 r => Reduc::Left(Box::new(r))
@@ -114,20 +236,149 @@ pub fn strat_byname(ex: &Exp) -> Reduc {
         _ => Reduc::Irred
     }
 }
-pub fn strat_norm(ex: &Exp) -> Reduc {
-    match ex {
-        Call(a, b) => match **a {
-            Lamb(_, _) => Reduc::Beta,
-            _ => match strat_norm(a) {
-                Reduc::Irred => wrap_red(Reduc::Right, strat_norm(b)),
+pub fn strat_norm<T: Term>(ex: &T) -> Reduc {
+    norm(ex, false)
+}
+pub fn strat_norm_eta<T: Term>(ex: &T) -> Reduc {
+    norm(ex, true)
+}
+fn norm<T: Term>(ex: &T, eta: bool) -> Reduc {
+    match ex.view() {
+        View::Call(a, b) => match a.view() {
+            View::Lamb(_) => Reduc::Beta,
+            _ => match norm(a, eta) {
+                Reduc::Irred => wrap_red(Reduc::Right, norm(b, eta)),
                 r => Reduc::Left(Box::new(r))
             }
         }
-        Lamb(_, r) => wrap_red(Reduc::Body, strat_norm(r)),
+        View::Lamb(_) if eta && ex.is_eta() => Reduc::Eta,
+        View::Lamb(r) => wrap_red(Reduc::Body, norm(r, eta)),
+        View::Var => Reduc::Irred
+    }
+}
+pub fn strat_applic<T: Term>(ex: &T) -> Reduc {
+    applic(ex, false)
+}
+pub fn strat_applic_eta<T: Term>(ex: &T) -> Reduc {
+    applic(ex, true)
+}
+fn applic<T: Term>(ex: &T, eta: bool) -> Reduc {
+    match ex.view() {
+        View::Call(a, b) => match applic(a, eta) {
+            Reduc::Irred => match applic(b, eta) {
+                Reduc::Irred => match a.view() {
+                    View::Lamb(_) => Reduc::Beta,
+                    _ => Reduc::Irred
+                }
+                r => Reduc::Right(Box::new(r))
+            }
+            r => Reduc::Left(Box::new(r))
+        }
+        View::Lamb(r) => match applic(r, eta) {
+            Reduc::Irred if eta && ex.is_eta() => Reduc::Eta,
+            Reduc::Irred => Reduc::Irred,
+            red => Reduc::Body(Box::new(red))
+        }
+        View::Var => Reduc::Irred
+    }
+}
+pub fn strat_value<T: Term>(ex: &T) -> Reduc {
+    match ex.view() {
+        View::Call(a, b) => match strat_value(a) {
+            Reduc::Irred => match strat_value(b) {
+                Reduc::Irred => match a.view() {
+                    View::Lamb(_) => Reduc::Beta,
+                    _ => Reduc::Irred
+                }
+                r => Reduc::Right(Box::new(r))
+            }
+            r => Reduc::Left(Box::new(r))
+        }
         _ => Reduc::Irred
     }
 }
 
+pub fn strat_hnf<T: Term>(ex: &T) -> Reduc {
+    match ex.view() {
+        View::Call(a, _) => match a.view() {
+            View::Lamb(_) => Reduc::Beta,
+            _ => wrap_red(Reduc::Left, strat_hnf(a))
+        }
+        View::Lamb(r) => wrap_red(Reduc::Body, strat_hnf(r)),
+        View::Var => Reduc::Irred
+    }
+}
+pub fn strat_whnf<T: Term>(ex: &T) -> Reduc {
+    match ex.view() {
+        View::Call(a, _) => match a.view() {
+            View::Lamb(_) => Reduc::Beta,
+            _ => wrap_red(Reduc::Left, strat_whnf(a))
+        }
+        _ => Reduc::Irred
+    }
+}
+
+/// Finds every redex in a term, outermost first and then left to right.
+/// Eta redexes are included if `eta` is set.
+pub fn redexes<T: Term>(ex: &T, eta: bool) -> Vec<Reduc> {
+    let mut found = Vec::new();
+    match ex.view() {
+        View::Call(a, b) => {
+            if let View::Lamb(_) = a.view() {
+                found.push(Reduc::Beta);
+            }
+            found.extend(redexes(a, eta).into_iter().map(|r| Reduc::Left(Box::new(r))));
+            found.extend(redexes(b, eta).into_iter().map(|r| Reduc::Right(Box::new(r))));
+        }
+        View::Lamb(r) => {
+            if eta && ex.is_eta() {
+                found.push(Reduc::Eta);
+            }
+            found.extend(redexes(r, eta).into_iter().map(|r| Reduc::Body(Box::new(r))));
+        }
+        View::Var => {}
+    }
+    found
+}
+
+/// Checks whether an expression contains no beta redexes.
+pub fn is_normal<T: Term>(ex: &T) -> bool {
+    match ex.view() {
+        View::Var => true,
+        View::Call(a, b) => match a.view() {
+            View::Lamb(_) => false,
+            _ => is_normal(a) && is_normal(b)
+        }
+        View::Lamb(r) => is_normal(r)
+    }
+}
+/// Checks whether an expression is in head normal form, `\x1 ... xn. y M1 ... Mk`.
+pub fn is_hnf<T: Term>(ex: &T) -> bool {
+    match ex.view() {
+        View::Lamb(r) => is_hnf(r),
+        _ => is_whnf(ex)
+    }
+}
+/// Checks whether an expression is in weak head normal form, i.e. a lambda
+/// abstraction or an application with a variable at its head.
+pub fn is_whnf<T: Term>(ex: &T) -> bool {
+    match ex.view() {
+        View::Var | View::Lamb(_) => true,
+        View::Call(a, _) => match a.view() {
+            View::Lamb(_) => false,
+            _ => is_whnf(a)
+        }
+    }
+}
+
+/// Checks whether `\x. a b` is an eta redex, i.e. `b` is `x` and `x` is not free in `a`.
+fn is_eta(x: &str, a: &Exp, b: &Exp) -> bool {
+    match b {
+        Var(n) => n == x && !free_in(x, a),
+        _ => false
+    }
+}
+
 pub fn free_in(var: &str, ex: &Exp) -> bool {
     match ex {
         Var(n) => {
@@ -142,78 +393,132 @@ pub fn free_in(var: &str, ex: &Exp) -> bool {
     }
 }
 
-pub fn sub(ex: Exp, name: &str, new: &Exp) -> Exp {
+/// Replaces the free occurrences of `name` in `ex` with `new`, renaming
+/// binders in the style `names` where they would capture its variables.
+pub fn sub(ex: Exp, name: &str, new: &Exp, names: NameStyle) -> Exp {
     match ex {
         Var(n) => if name == n {
             new.clone()
         } else {
             Var(n)
         }
-        Call(a, b) => Call(Box::new(sub(*a, name, new)), Box::new(sub(*b, name, new))),
-        Lamb(x, r) => if name == x {
+        Call(a, b) => Call(Box::new(sub(*a, name, new, names)), Box::new(sub(*b, name, new, names))),
+        Lamb(x, r) => if name == x || !free_in(name, &r) {
             Lamb(x, r)
         } else if free_in(&x, new) {
-            let mut x_new = x.clone();
-            x_new.push('\'');
-            sub(alpha(Lamb(x, r), x_new), name, new)
+            let x_new = fresh(&x, names, |n| free_in(n, new) || free_in(n, &r));
+            Lamb(x_new.clone(), Box::new(sub(sub(*r, &x, &Var(x_new), names), name, new, names)))
         } else {
-            Lamb(x, Box::new(sub(*r, name, new)))
+            Lamb(x, Box::new(sub(*r, name, new, names)))
         }
     }
 }
 
-fn alpha(ex: Exp, new: String) -> Exp {
-    if let Lamb(x, r) = ex {
-        Lamb(new.clone(), Box::new(sub(*r, &x, &Var(new))))
-    }
-    else {
-        panic!("{} is not a lambda expression", ex)
+/// How fresh variable names are derived from the name they replace.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum NameStyle {
+    /// `x`, `x'`, `x''`, ...
+    #[default]
+    Prime,
+    /// `x`, `x1`, `x2`, ...
+    Number,
+    /// `x`, `x₁`, `x₂`, ...
+    Subscript,
+}
+
+const SUBSCRIPTS: [char; 10] = ['₀', '₁', '₂', '₃', '₄', '₅', '₆', '₇', '₈', '₉'];
+
+/// Picks a variant of `x` for which `taken` is false, in the naming style `style`.
+pub fn fresh(x: &str, style: NameStyle, taken: impl Fn(&str) -> bool) -> String {
+    let stem = match style {
+        NameStyle::Prime => x,
+        NameStyle::Number => x.trim_end_matches(|c: char| c.is_ascii_digit()),
+        NameStyle::Subscript => x.trim_end_matches(|c| SUBSCRIPTS.contains(&c)),
+    };
+    let stem = if stem.is_empty() { x } else { stem };
+    let mut i: usize = 1;
+    loop {
+        let name = match style {
+            NameStyle::Prime => format!("{}{}", stem, "'".repeat(i)),
+            NameStyle::Number => format!("{}{}", stem, i),
+            NameStyle::Subscript => format!("{}{}", stem,
+                i.to_string().bytes().map(|d| SUBSCRIPTS[(d - b'0') as usize]).collect::<String>()),
+        };
+        if name != x && !taken(&name) {
+            return name;
+        }
+        i += 1;
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::code::assert_alpha_eq;
     use crate::parser::{ parse, ParseError };
+    use crate::debruijn::DExp;
+
+    fn full<T: Term + fmt::Debug>(strat: Strategy<T>, ex: T) -> T {
+        reduce_full(strat, ex, Limits::default(), T::Names::default()).unwrap().0
+    }
 
     #[test]
     fn reductions() -> Result<(), ParseError> {
-        assert_eq!(reduce_with(parse("x")?, &Reduc::Irred), parse("x")?);
-        assert_eq!(reduce_with(parse("(\\x. y x) z")?, &Reduc::Irred), parse("(\\x. y x) z")?);
-        assert_eq!(reduce_with(parse("(\\x. y x) z")?, &Reduc::Beta), parse("y z")?);
-        assert_eq!(reduce_with(parse("((\\x z. y x z) z)")?, &Reduc::Beta), parse("\\z'. y z z'")?);
-        assert_eq!(reduce_with(parse("(\\a. a) b ((\\x. x) y)")?, &Reduc::Left(Box::new(Reduc::Beta))),
+        assert_eq!(reduce_with(parse("x")?, &Reduc::Irred, NameStyle::Prime), parse("x")?);
+        assert_eq!(reduce_with(parse("(\\x. y x) z")?, &Reduc::Irred, NameStyle::Prime), parse("(\\x. y x) z")?);
+        assert_eq!(reduce_with(parse("(\\x. y x) z")?, &Reduc::Beta, NameStyle::Prime), parse("y z")?);
+        assert_eq!(reduce_with(parse("((\\x z. y x z) z)")?, &Reduc::Beta, NameStyle::Prime), parse("\\z'. y z z'")?);
+        assert_eq!(reduce_with(parse("(\\a. a) b ((\\x. x) y)")?, &Reduc::Left(Box::new(Reduc::Beta)), NameStyle::Prime),
             parse("b ((\\x. x) y)")?);
-        assert_eq!(reduce_with(parse("(\\a. a) b ((\\x. x) y)")?, &Reduc::Right(Box::new(Reduc::Beta))),
+        assert_eq!(reduce_with(parse("(\\a. a) b ((\\x. x) y)")?, &Reduc::Right(Box::new(Reduc::Beta)), NameStyle::Prime),
             parse("(\\a. a) b y")?);
         Ok(())
     }
     #[test]
+    fn all_redexes() -> Result<(), ParseError> {
+        let b = |r| Box::new(r);
+        assert_eq!(redexes(&parse("(\\x. (\\y. y) x) ((\\z. z) w)")?, false), vec![
+            Reduc::Beta,
+            Reduc::Left(b(Reduc::Body(b(Reduc::Beta)))),
+            Reduc::Right(b(Reduc::Beta)),
+        ]);
+        assert_eq!(redexes(&parse("\\x. f x")?, false), vec![]);
+        assert_eq!(redexes(&parse("\\x. f x")?, true), vec![Reduc::Eta]);
+        // every redex found can be contracted, including in the nameless representation
+        let ex = parse("(\\x y. x y) (\\z. (\\a. a) z)")?;
+        for red in redexes(&ex, true) {
+            let named = reduce_with(ex.clone(), &red, NameStyle::Prime);
+            assert_eq!(DExp::from_exp(&named), DExp::from_exp(&ex).reduce_with(&red, ()));
+        }
+        Ok(())
+    }
+    #[test]
     fn step_byname() -> Result<(), ParseError> {
-        assert_eq!(reduce_step(strat_byname, parse("x")?), (Reduc::Irred, parse("x")?));
-        assert_eq!(reduce_step(strat_byname, parse("(\\a. a) b ((\\x. x) y)")?),
+        assert_eq!(reduce_step(strat_byname, parse("x")?, NameStyle::Prime), (Reduc::Irred, parse("x")?));
+        assert_eq!(reduce_step(strat_byname, parse("(\\a. a) b ((\\x. x) y)")?, NameStyle::Prime),
             (Reduc::Left(Box::new(Reduc::Beta)), parse("b ((\\x. x) y)")?));
         Ok(())
     }
     #[test]
     fn skk_step_byname() -> Result<(), ParseError> {
-        assert_eq!(reduce_step(strat_byname, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)")?),
+        assert_eq!(reduce_step(strat_byname, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)")?, NameStyle::Prime),
             (Reduc::Left(Box::new(Reduc::Beta)), parse("(\\K. (\\x y z. x z (y z)) K K) (\\x y. x)")?));
 
-        assert_eq!(reduce_step(strat_byname, parse("(\\K. (\\x y z. x z (y z)) K K) (\\x y. x)")?),
+        assert_eq!(reduce_step(strat_byname, parse("(\\K. (\\x y z. x z (y z)) K K) (\\x y. x)")?, NameStyle::Prime),
             (Reduc::Beta, parse("(\\x y z. x z (y z)) (\\x y. x) (\\x y. x)")?));
 
-        assert_eq!(reduce_step(strat_byname, parse("(\\x y z. x z (y z)) (\\x y. x) (\\x y. x)")?),
+        assert_eq!(reduce_step(strat_byname, parse("(\\x y z. x z (y z)) (\\x y. x) (\\x y. x)")?, NameStyle::Prime),
             (Reduc::Left(Box::new(Reduc::Beta)), parse("(\\y z. (\\x y. x) z (y z)) (\\x y. x)")?));
 
-        assert_eq!(reduce_step(strat_byname, parse("(\\y z. (\\x y. x) z (y z)) (\\x y. x)")?),
+        assert_eq!(reduce_step(strat_byname, parse("(\\y z. (\\x y. x) z (y z)) (\\x y. x)")?, NameStyle::Prime),
             (Reduc::Beta, parse("\\z. (\\x y. x) z ((\\x y. x) z)")?));
 
-        assert_eq!(reduce_step(strat_byname, parse("\\z. (\\x y. x) z ((\\x y. x) z)")?),
+        assert_eq!(reduce_step(strat_byname, parse("\\z. (\\x y. x) z ((\\x y. x) z)")?, NameStyle::Prime),
             (Reduc::Irred, parse("\\z. (\\x y. x) z ((\\x y. x) z)")?));
         Ok(())
     }
     #[test]
+    #[allow(clippy::useless_conversion)]
     fn skk_iter_byname() -> Result<(), ParseError> {
         let steps: Vec<(Reduc, Exp)> = reduce_iter(strat_byname, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)")?).into_iter().collect();
         assert_eq!(steps,
@@ -227,17 +532,17 @@ mod tests {
     }
     #[test]
     fn skk_full_byname() -> Result<(), ParseError> {
-        assert_eq!(reduce_full(strat_byname, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)")?),
+        assert_eq!(full(strat_byname, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)")?),
             parse("\\z. (\\x y. x) z ((\\x y. x) z)")?);
-        assert_eq!(reduce_full(strat_byname, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x) a")?),
+        assert_eq!(full(strat_byname, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x) a")?),
             parse("a")?);
         Ok(())
     }
     #[test]
     fn skk_steps_byname() -> Result<(), ParseError> {
-        assert_eq!(reduce_full(strat_byname, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)")?),
+        assert_eq!(full(strat_byname, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)")?),
             parse("\\z. (\\x y. x) z ((\\x y. x) z)")?);
-        assert_eq!(reduce_full(strat_byname, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x) a")?),
+        assert_eq!(full(strat_byname, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x) a")?),
             parse("a")?);
         Ok(())
     }
@@ -268,21 +573,22 @@ mod tests {
     }
     #[test]
     fn normalization_byname() -> Result<(), ParseError> {
-        assert_eq!(reduce_full(strat_byname, parse("(\\a b. b) ((\\x. x x) (\\x. x x)) z")?),
+        assert_eq!(full(strat_byname, parse("(\\a b. b) ((\\x. x x) (\\x. x x)) z")?),
             parse("z")?);
-        assert_eq!(reduce_full(strat_byname, parse("(λf. f ((λx. x x) (λx. x x)) z) (λa b. b)")?),
+        assert_eq!(full(strat_byname, parse("(λf. f ((λx. x x) (λx. x x)) z) (λa b. b)")?),
             parse("z")?);
         Ok(())
     }
 
     #[test]
     fn step_norm() -> Result<(), ParseError> {
-        assert_eq!(reduce_step(strat_norm, parse("x")?), (Reduc::Irred, parse("x")?));
-        assert_eq!(reduce_step(strat_norm, parse("(\\a. a) b ((\\x. x) y)")?),
+        assert_eq!(reduce_step(strat_norm, parse("x")?, NameStyle::Prime), (Reduc::Irred, parse("x")?));
+        assert_eq!(reduce_step(strat_norm, parse("(\\a. a) b ((\\x. x) y)")?, NameStyle::Prime),
             (Reduc::Left(Box::new(Reduc::Beta)), parse("b ((\\x. x) y)")?));
         Ok(())
     }
     #[test]
+    #[allow(clippy::useless_conversion)]
     fn skk_iter_norm() -> Result<(), ParseError> {
         let steps: Vec<(String, Exp)> = reduce_iter(strat_norm,
             parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)")?)
@@ -300,17 +606,17 @@ mod tests {
     }
     #[test]
     fn skk_full_norm() -> Result<(), ParseError> {
-        assert_eq!(reduce_full(strat_norm, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)")?),
+        assert_eq!(full(strat_norm, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)")?),
             parse("(\\z. z)")?);
-        assert_eq!(reduce_full(strat_norm, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x) a")?),
+        assert_eq!(full(strat_norm, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x) a")?),
             parse("a")?);
         Ok(())
     }
     #[test]
     fn normalization_norm() -> Result<(), ParseError> {
-        assert_eq!(reduce_full(strat_norm, parse("(\\a b. b) ((\\x. x x) (\\x. x x)) z")?),
+        assert_eq!(full(strat_norm, parse("(\\a b. b) ((\\x. x x) (\\x. x x)) z")?),
             parse("z")?);
-        assert_eq!(reduce_full(strat_norm, parse("(λf. f ((λx. x x) (λx. x x)) z) (λa b. b)")?),
+        assert_eq!(full(strat_norm, parse("(λf. f ((λx. x x) (λx. x x)) z) (λa b. b)")?),
             parse("z")?);
         Ok(())
     }
@@ -342,6 +648,295 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn step_applic() -> Result<(), ParseError> {
+        assert_eq!(reduce_step(strat_applic, parse("x")?, NameStyle::Prime), (Reduc::Irred, parse("x")?));
+        assert_eq!(reduce_step(strat_applic, parse("(\\a. a) b ((\\x. x) y)")?, NameStyle::Prime),
+            (Reduc::Left(Box::new(Reduc::Beta)), parse("b ((\\x. x) y)")?));
+        Ok(())
+    }
+    #[test]
+    fn skk_iter_applic() -> Result<(), ParseError> {
+        let steps: Vec<(String, Exp)> = reduce_iter(strat_applic,
+            parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)")?)
+            .map(|(red, ex)| (format!("{}", red), ex)).collect();
+        assert_eq!(steps,
+            vec![
+                ("(β _)".to_string(), parse("(\\K. (\\x y z. x z (y z)) K K) (\\x y. x)")?),
+                ("((\\. (β _)) _)".to_string(), parse("(\\K. (\\y z. K z (y z)) K) (\\x y. x)")?),
+                ("((\\. β) _)".to_string(), parse("(\\K. \\z. K z (K z)) (\\x y. x)")?),
+                ("β".to_string(), parse("\\z. (\\x y. x) z ((\\x y. x) z)")?),
+                ("(\\. (β _))".to_string(), parse("\\z. (\\y. z) ((\\x y. x) z)")?),
+                ("(\\. (_ β))".to_string(), parse("\\z. (\\y. z) (\\y. z)")?),
+                ("(\\. β)".to_string(), parse("\\z. z")?),
+            ]);
+        Ok(())
+    }
+    #[test]
+    fn skk_full_applic() -> Result<(), ParseError> {
+        assert_eq!(full(strat_applic, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)")?),
+            parse("(\\z. z)")?);
+        assert_eq!(full(strat_applic, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x) a")?),
+            parse("a")?);
+        Ok(())
+    }
+    #[test]
+    fn divergence_applic() -> Result<(), ParseError> {
+        let omega = parse("(\\b. z) ((\\x. x x) (\\x. x x))")?;
+        assert_eq!(reduce_step(strat_applic, omega.clone(), NameStyle::Prime),
+            (Reduc::Right(Box::new(Reduc::Beta)), omega));
+        Ok(())
+    }
+    #[test]
+    fn irstrat_applic() -> Result<(), ParseError> {
+        assert_eq!(strat_applic(&parse("x")?), Reduc::Irred);
+        assert_eq!(strat_applic(&parse("a b")?), Reduc::Irred);
+        assert_eq!(strat_applic(&parse("\\x.x")?), Reduc::Irred);
+        assert_ne!(strat_applic(&parse("\\x. (\\y.y) z")?), Reduc::Irred);
+        Ok(())
+    }
+    #[test]
+    fn beta_applic() -> Result<(), ParseError> {
+        assert_eq!(strat_applic(&parse("(\\x. x) y")?), Reduc::Beta);
+        assert_eq!(strat_applic(&parse("(\\x. x) y z")?),
+            Reduc::Left(Box::new(Reduc::Beta)));
+        assert_eq!(strat_applic(&parse("z ((\\x. x) y)")?),
+            Reduc::Right(Box::new(Reduc::Beta)));
+        assert_eq!(strat_applic(&parse("\\x. (\\y.y) z")?),
+            Reduc::Body(Box::new(Reduc::Beta)));
+        Ok(())
+    }
+    #[test]
+    fn order_applic() -> Result<(), ParseError> {
+        assert_eq!(strat_applic(&parse("(\\x. x) ((\\y. y) z)")?),
+            Reduc::Right(Box::new(Reduc::Beta)));
+        assert_eq!(strat_applic(&parse("(\\x. (\\a. a) x y) z")?),
+            Reduc::Left(Box::new(Reduc::Body(Box::new(Reduc::Left(Box::new(Reduc::Beta)))))));
+        Ok(())
+    }
+
+    #[test]
+    fn step_value() -> Result<(), ParseError> {
+        assert_eq!(reduce_step(strat_value, parse("x")?, NameStyle::Prime), (Reduc::Irred, parse("x")?));
+        assert_eq!(reduce_step(strat_value, parse("(\\a. a) b ((\\x. x) y)")?, NameStyle::Prime),
+            (Reduc::Left(Box::new(Reduc::Beta)), parse("b ((\\x. x) y)")?));
+        Ok(())
+    }
+    #[test]
+    fn skk_iter_value() -> Result<(), ParseError> {
+        let steps: Vec<(String, Exp)> = reduce_iter(strat_value,
+            parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)")?)
+            .map(|(red, ex)| (format!("{}", red), ex)).collect();
+        assert_eq!(steps,
+            vec![
+                ("(β _)".to_string(), parse("(\\K. (\\x y z. x z (y z)) K K) (\\x y. x)")?),
+                ("β".to_string(), parse("(\\x y z. x z (y z)) (\\x y. x) (\\x y. x)")?),
+                ("(β _)".to_string(), parse("(\\y z. (\\x y. x) z (y z)) (\\x y. x)")?),
+                ("β".to_string(), parse("\\z. (\\x y. x) z ((\\x y. x) z)")?),
+            ]);
+        Ok(())
+    }
+    #[test]
+    fn skk_full_value() -> Result<(), ParseError> {
+        assert_eq!(full(strat_value, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)")?),
+            parse("\\z. (\\x y. x) z ((\\x y. x) z)")?);
+        assert_eq!(full(strat_value, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x) a")?),
+            parse("a")?);
+        Ok(())
+    }
+    #[test]
+    fn divergence_value() -> Result<(), ParseError> {
+        let omega = parse("(\\b. z) ((\\x. x x) (\\x. x x))")?;
+        assert_eq!(reduce_step(strat_value, omega.clone(), NameStyle::Prime),
+            (Reduc::Right(Box::new(Reduc::Beta)), omega));
+        Ok(())
+    }
+    #[test]
+    fn irstrat_value() -> Result<(), ParseError> {
+        assert_eq!(strat_value(&parse("x")?), Reduc::Irred);
+        assert_eq!(strat_value(&parse("a b")?), Reduc::Irred);
+        assert_eq!(strat_value(&parse("\\x.x")?), Reduc::Irred);
+        assert_eq!(strat_value(&parse("\\x. (\\y.y) z")?), Reduc::Irred);
+        Ok(())
+    }
+    #[test]
+    fn beta_value() -> Result<(), ParseError> {
+        assert_eq!(strat_value(&parse("(\\x. x) y")?), Reduc::Beta);
+        assert_eq!(strat_value(&parse("(\\x. x) y z")?),
+            Reduc::Left(Box::new(Reduc::Beta)));
+        assert_eq!(strat_value(&parse("z ((\\x. x) y)")?),
+            Reduc::Right(Box::new(Reduc::Beta)));
+        Ok(())
+    }
+    #[test]
+    fn order_value() -> Result<(), ParseError> {
+        assert_eq!(strat_value(&parse("(\\x. x) ((\\y. y) z)")?),
+            Reduc::Right(Box::new(Reduc::Beta)));
+        assert_eq!(strat_value(&parse("(\\x. (\\a. a) x y) z")?),
+            Reduc::Beta);
+        Ok(())
+    }
+
+    #[test]
+    fn eta_reductions() -> Result<(), ParseError> {
+        assert_eq!(reduce_with(parse("\\x. f x")?, &Reduc::Eta, NameStyle::Prime), parse("f")?);
+        assert_eq!(reduce_with(parse("\\x. (\\y. y) x")?, &Reduc::Eta, NameStyle::Prime), parse("\\y. y")?);
+        assert_eq!(reduce_with(parse("\\y. \\x. f x")?, &Reduc::Body(Box::new(Reduc::Eta)), NameStyle::Prime),
+            parse("\\y. f")?);
+        Ok(())
+    }
+    #[test]
+    fn irstrat_eta() -> Result<(), ParseError> {
+        assert_eq!(strat_norm(&parse("\\x. f x")?), Reduc::Irred);
+        assert_eq!(strat_applic(&parse("\\x. f x")?), Reduc::Irred);
+        assert_eq!(strat_norm_eta(&parse("\\x. x x")?), Reduc::Irred);
+        assert_eq!(strat_norm_eta(&parse("\\x. f x y")?), Reduc::Irred);
+        assert_eq!(strat_applic_eta(&parse("\\x. x x")?), Reduc::Irred);
+        assert_eq!(strat_applic_eta(&parse("\\x. f y")?), Reduc::Irred);
+        Ok(())
+    }
+    #[test]
+    fn order_norm_eta() -> Result<(), ParseError> {
+        assert_eq!(strat_norm_eta(&parse("\\x. f x")?), Reduc::Eta);
+        assert_eq!(strat_norm_eta(&parse("\\x. (\\y. f y) x")?), Reduc::Eta);
+        assert_eq!(strat_norm_eta(&parse("(\\x. f x) y")?), Reduc::Beta);
+        Ok(())
+    }
+    #[test]
+    fn order_applic_eta() -> Result<(), ParseError> {
+        assert_eq!(strat_applic_eta(&parse("\\x. f x")?), Reduc::Eta);
+        assert_eq!(strat_applic_eta(&parse("\\x. (\\y. f y) x")?),
+            Reduc::Body(Box::new(Reduc::Left(Box::new(Reduc::Eta)))));
+        assert_eq!(strat_applic_eta(&parse("(\\x. f x) y")?),
+            Reduc::Left(Box::new(Reduc::Eta)));
+        Ok(())
+    }
+    #[test]
+    fn skk_iter_norm_eta() -> Result<(), ParseError> {
+        let steps: Vec<(String, Exp)> = reduce_iter(strat_norm_eta,
+            parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x) \\a b. f a b")?)
+            .map(|(red, ex)| (format!("{}", red), ex)).collect();
+        assert_eq!(steps.last(), Some(&("η".to_string(), parse("f")?)));
+        assert_eq!(full(strat_norm_eta, parse("\\x. (\\y. f y) x")?), parse("f")?);
+        assert_eq!(full(strat_applic_eta, parse("\\x. (\\y. f y) x")?), parse("f")?);
+        Ok(())
+    }
+
+    #[test]
+    fn skk_iter_hnf() -> Result<(), ParseError> {
+        let steps: Vec<(String, Exp)> = reduce_iter(strat_hnf,
+            parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)")?)
+            .map(|(red, ex)| (format!("{}", red), ex)).collect();
+        assert_eq!(steps,
+            vec![
+                ("(β _)".to_string(), parse("(\\K. (\\x y z. x z (y z)) K K) (\\x y. x)")?),
+                ("β".to_string(), parse("(\\x y z. x z (y z)) (\\x y. x) (\\x y. x)")?),
+                ("(β _)".to_string(), parse("(\\y z. (\\x y. x) z (y z)) (\\x y. x)")?),
+                ("β".to_string(), parse("\\z. (\\x y. x) z ((\\x y. x) z)")?),
+                ("(\\. (β _))".to_string(), parse("\\z. (\\y. z) ((\\x y. x) z)")?),
+                ("(\\. β)".to_string(), parse("\\z. z")?),
+            ]);
+        Ok(())
+    }
+    #[test]
+    fn stops_hnf() -> Result<(), ParseError> {
+        assert_eq!(strat_hnf(&parse("\\x. x ((\\y. y) z)")?), Reduc::Irred);
+        assert_eq!(strat_hnf(&parse("\\x. (\\y. y) x")?), Reduc::Body(Box::new(Reduc::Beta)));
+        assert_eq!(full(strat_hnf, parse("(\\f. \\x. x (f f)) (\\x. x x)")?),
+            parse("\\x. x ((\\x. x x) (\\x. x x))")?);
+        Ok(())
+    }
+    #[test]
+    fn skk_iter_whnf() -> Result<(), ParseError> {
+        let steps: Vec<(String, Exp)> = reduce_iter(strat_whnf,
+            parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)")?)
+            .map(|(red, ex)| (format!("{}", red), ex)).collect();
+        assert_eq!(steps,
+            vec![
+                ("(β _)".to_string(), parse("(\\K. (\\x y z. x z (y z)) K K) (\\x y. x)")?),
+                ("β".to_string(), parse("(\\x y z. x z (y z)) (\\x y. x) (\\x y. x)")?),
+                ("(β _)".to_string(), parse("(\\y z. (\\x y. x) z (y z)) (\\x y. x)")?),
+                ("β".to_string(), parse("\\z. (\\x y. x) z ((\\x y. x) z)")?),
+            ]);
+        Ok(())
+    }
+    #[test]
+    fn stops_whnf() -> Result<(), ParseError> {
+        assert_eq!(strat_whnf(&parse("\\x. (\\y. y) x")?), Reduc::Irred);
+        assert_eq!(strat_whnf(&parse("z ((\\x. x) y)")?), Reduc::Irred);
+        assert_eq!(full(strat_whnf, parse("(\\a b. a) z ((\\x. x x) (\\x. x x))")?),
+            parse("z")?);
+        Ok(())
+    }
+    #[test]
+    fn predicates() -> Result<(), ParseError> {
+        assert!(is_normal(&parse("\\x. x (y z)")?));
+        assert!(!is_normal(&parse("\\x. x ((\\y. y) z)")?));
+        assert!(is_hnf(&parse("\\x. x ((\\y. y) z)")?));
+        assert!(!is_hnf(&parse("\\x. (\\y. y) x")?));
+        assert!(is_whnf(&parse("\\x. (\\y. y) x")?));
+        assert!(is_whnf(&parse("x ((\\y. y) z)")?));
+        assert!(!is_whnf(&parse("(\\y. y) x z")?));
+        Ok(())
+    }
+    #[test]
+    fn predicates_agree() -> Result<(), ParseError> {
+        for src in &["(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)",
+                     "(\\a b. a) z ((\\x. x x) (\\x. x x))",
+                     "(\\f. \\x. x (f f)) (\\x. x x)"] {
+            assert!(is_hnf(&full(strat_hnf, parse(src)?)));
+            assert!(is_whnf(&full(strat_whnf, parse(src)?)));
+        }
+        assert!(is_normal(&full(strat_norm, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)")?)));
+        Ok(())
+    }
+
+    #[test]
+    fn limit_steps() -> Result<(), ParseError> {
+        let limits = Limits { steps: Some(10), ..Limits::default() };
+        assert_eq!(reduce_full(strat_norm, parse("(\\x. x x) (\\x. x x)")?, limits, NameStyle::Prime),
+            Err(Exhausted { budget: Budget::Steps, steps: 10, ex: parse("(\\x. x x) (\\x. x x)")? }));
+        assert_eq!(reduce_full(strat_norm, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)")?, limits, NameStyle::Prime),
+            Ok((parse("\\z. z")?, 6)));
+        let limits = Limits { steps: Some(6), ..Limits::default() };
+        assert_eq!(reduce_full(strat_norm, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)")?, limits, NameStyle::Prime),
+            Ok((parse("\\z. z")?, 6)));
+        Ok(())
+    }
+    #[test]
+    fn limit_size() -> Result<(), ParseError> {
+        let limits = Limits { size: Some(50), ..Limits::default() };
+        match reduce_full(strat_norm, parse("(\\x. x x x) (\\x. x x x)")?, limits, NameStyle::Prime) {
+            Err(Exhausted { budget: Budget::Size, ex, .. }) => assert!(ex.size() > 50),
+            r => panic!("expected size limit, got {:?}", r)
+        }
+        Ok(())
+    }
+    #[test]
+    fn limit_iter() -> Result<(), ParseError> {
+        let mut iter = reduce_iter(strat_norm, parse("(\\x. x x) (\\x. x x)")?)
+            .limit(Limits { steps: Some(3), ..Limits::default() });
+        assert_eq!(iter.by_ref().count(), 3);
+        assert_eq!(iter.exhausted(), Some(Budget::Steps));
+        assert_eq!(iter.steps(), 3);
+
+        let mut iter = reduce_iter(strat_norm, parse("(\\S K. S K K) (\\x y z. x z (y z)) (\\x y. x)")?)
+            .limit(Limits { steps: Some(6), ..Limits::default() });
+        assert_eq!(iter.by_ref().count(), 6);
+        assert_eq!(iter.exhausted(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn limit_interrupt() -> Result<(), ParseError> {
+        static FLAG: AtomicBool = AtomicBool::new(true);
+        let limits = Limits { interrupt: Some(&FLAG), ..Limits::default() };
+        assert_eq!(reduce_full(strat_norm, parse("(\\x. x x) (\\x. x x)")?, limits, NameStyle::Prime),
+            Err(Exhausted { budget: Budget::Interrupt, steps: 0, ex: parse("(\\x. x x) (\\x. x x)")? }));
+        assert_eq!(reduce_full(strat_norm, parse("y")?, limits, NameStyle::Prime), Ok((parse("y")?, 0)));
+        Ok(())
+    }
+
     #[test]
     fn free() -> Result<(), ParseError> {
         assert!(free_in("x", &parse("x")?));
@@ -353,12 +948,65 @@ mod tests {
         Ok(())
     }
     #[test]
+    fn substitution_alpha() -> Result<(), ParseError> {
+        assert_alpha_eq!(reduce_with(parse("((\\x z. y x z) z)")?, &Reduc::Beta, NameStyle::Prime), parse("\\w. y z w")?);
+        assert_alpha_eq!(sub(parse("\\x. x z")?, "z", &parse("x")?, NameStyle::Prime), parse("\\y. y x")?);
+        Ok(())
+    }
+    #[test]
+    fn substitution_fresh() -> Result<(), ParseError> {
+        assert_alpha_eq!(sub(parse("\\x. x' z")?, "z", &parse("x")?, NameStyle::Prime), parse("\\w. x' x")?);
+        assert_alpha_eq!(sub(parse("\\x. z x'")?, "z", &parse("x x'")?, NameStyle::Prime), parse("\\w. x x' x'")?);
+        assert_eq!(sub(parse("\\x. x' z")?, "z", &parse("x")?, NameStyle::Prime), parse("\\x''. x' x")?);
+        assert_eq!(sub(parse("\\x. x1 z")?, "z", &parse("x")?, NameStyle::Number), parse("\\x2. x1 x")?);
+        assert_eq!(sub(parse("\\x1. z")?, "z", &parse("x1")?, NameStyle::Number), parse("\\x2. x1")?);
+        assert_eq!(sub(parse("\\x. z x")?, "z", &parse("x")?, NameStyle::Subscript), parse("\\x₁. x x₁")?);
+        Ok(())
+    }
+
+    /// A small xorshift generator, so the property tests are reproducible.
+    struct Rng(u64);
+    impl Rng {
+        fn next(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+        fn name(&mut self) -> String {
+            // names chosen to collide with each other's fresh variants
+            ["x", "x'", "x''", "y", "x1", "x2", "z"][self.next(7)].to_string()
+        }
+        fn term(&mut self, depth: usize) -> Exp {
+            match if depth == 0 { 0 } else { self.next(3) } {
+                0 => Var(self.name()),
+                1 => Call(Box::new(self.term(depth - 1)), Box::new(self.term(depth - 1))),
+                _ => Lamb(self.name(), Box::new(self.term(depth - 1))),
+            }
+        }
+    }
+    #[test]
+    fn substitution_never_captures() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for style in &[NameStyle::Prime, NameStyle::Number, NameStyle::Subscript] {
+            for _ in 0..2000 {
+                let (body, name, new) = (rng.term(5), rng.name(), rng.term(3));
+                // the nameless reducer has no names to capture, so it serves as the reference
+                let expected = DExp::from_exp(&Call(Box::new(Lamb(name.clone(), Box::new(body.clone()))),
+                    Box::new(new.clone()))).reduce_with(&Reduc::Beta, ());
+                let actual = sub(body.clone(), &name, &new, *style);
+                assert!(DExp::from_exp(&actual) == expected,
+                    "[{}:={}] {} gave {}, expected {}", name, new, body, actual, expected);
+            }
+        }
+    }
+    #[test]
     fn substitution() -> Result<(), ParseError> {
-        assert_eq!(sub(parse("x")?, "x", &parse("y")?), parse("y")?);
-        assert_eq!(sub(parse("x y")?, "x", &parse("z")?), parse("z y")?);
-        assert_eq!(sub(parse("\\x. x z")?, "z", &parse("w")?), parse("\\x. x w")?);
-        assert_eq!(sub(parse("\\x. x")?, "x", &parse("z")?), parse("\\x. x")?);
-        assert_eq!(sub(parse("\\x. x z")?, "z", &parse("x")?), parse("\\x'. x' x")?);
+        assert_eq!(sub(parse("x")?, "x", &parse("y")?, NameStyle::Prime), parse("y")?);
+        assert_eq!(sub(parse("x y")?, "x", &parse("z")?, NameStyle::Prime), parse("z y")?);
+        assert_eq!(sub(parse("\\x. x z")?, "z", &parse("w")?, NameStyle::Prime), parse("\\x. x w")?);
+        assert_eq!(sub(parse("\\x. x")?, "x", &parse("z")?, NameStyle::Prime), parse("\\x. x")?);
+        assert_eq!(sub(parse("\\x. x z")?, "z", &parse("x")?, NameStyle::Prime), parse("\\x'. x' x")?);
         Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,155 @@
+use crate::parser::{ parse_file, is_incomplete };
+use crate::session::{ Session, COMMANDS };
+
+use rustyline::{ Cmd, Context, Editor, Helper, KeyEvent };
+use rustyline::completion::{ Completer, FilenameCompleter, Pair };
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+
+use std::path::PathBuf;
+use std::sync::atomic::{ AtomicBool, Ordering };
+
+/// Characters that end a name when looking for the word to complete.
+const DELIMS: &[char] = &['(', ')', '\\', 'λ', '.', ';', '#'];
+
+/// Completes command names after `:`, file names after `:load`, and
+/// otherwise the names currently defined in the session.
+struct LambdaHelper {
+    names: Vec<String>,
+    files: FilenameCompleter,
+}
+impl Completer for LambdaHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        if before.trim_start().starts_with(":load ") {
+            return self.files.complete(line, pos, ctx);
+        }
+        let start = before.rfind(|c: char| c.is_whitespace() || DELIMS.contains(&c))
+            .map_or(0, |i| i + before[i..].chars().next().unwrap().len_utf8());
+        let word = &before[start..];
+        match word.strip_prefix(':') {
+            Some(cmd) if before[..start].trim().is_empty() =>
+                Ok((start + 1, pairs(COMMANDS.iter().copied(), cmd))),
+            _ => Ok((start, pairs(self.names.iter().map(String::as_str), word))),
+        }
+    }
+}
+impl Hinter for LambdaHelper {
+    type Hint = String;
+}
+impl Highlighter for LambdaHelper {}
+impl Validator for LambdaHelper {}
+impl Helper for LambdaHelper {}
+
+fn pairs<'a>(candidates: impl Iterator<Item = &'a str>, prefix: &str) -> Vec<Pair> {
+    candidates
+        .filter(|c| c.starts_with(prefix))
+        .map(|c| Pair { display: c.to_string(), replacement: c.to_string() })
+        .collect()
+}
+
+/// Checks whether an entry can't be parsed yet, but could be with more lines,
+/// or ends in a doc comment for a definition still to come.
+fn needs_more(inp: &str) -> bool {
+    !inp.trim_start().starts_with(':') && (
+        matches!(parse_file(inp), Err(errors) if errors.iter().all(|e| is_incomplete(e, inp))) ||
+        inp.trim_end().lines().last().is_some_and(|line| line.trim_start().starts_with("##"))
+    )
+}
+
+fn history_file() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".lambda_history"))
+}
+
+/// Reads and evaluates lines until the user quits. `interrupted` is reset
+/// before each line is evaluated.
+pub fn repl(session: &mut Session, interrupted: &AtomicBool) {
+    let mut editor = Editor::<LambdaHelper>::new();
+    editor.set_helper(Some(LambdaHelper { names: Vec::new(), files: FilenameCompleter::new() }));
+    // typing λ directly is awkward on most keyboards
+    editor.bind_sequence(KeyEvent::alt('l'), Cmd::Insert(1, "λ".to_string()));
+    let history = history_file();
+    if let Some(file) = &history {
+        // there's no history yet on first run
+        let _ = editor.load_history(file);
+    }
+    // the entry so far, if earlier lines left it incomplete
+    let mut inp = String::new();
+    loop {
+        if let Some(helper) = editor.helper_mut() {
+            helper.names = session.names().map(String::from).collect();
+        }
+        let line = match editor.readline(if inp.is_empty() { "λ> " } else { ".. " }) {
+            Ok(line) => line,
+            // Ctrl-C while editing just discards the entry
+            Err(ReadlineError::Interrupted) => {
+                inp.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => {
+                // still report what's wrong with an unfinished entry
+                if !inp.is_empty() {
+                    session.line(&inp);
+                }
+                break;
+            }
+            Err(e) => {
+                eprintln!("Error reading input: {}", e);
+                break;
+            }
+        };
+        if !line.trim().is_empty() {
+            editor.add_history_entry(line.as_str());
+        }
+        inp.push_str(&line);
+        inp.push('\n');
+        if needs_more(&inp) {
+            continue;
+        }
+        interrupted.store(false, Ordering::SeqCst);
+        let quit = !session.line(&inp);
+        inp.clear();
+        if quit {
+            break;
+        }
+    }
+    if let Some(file) = &history {
+        if let Err(e) = editor.save_history(file) {
+            eprintln!("Couldn't save history to {}: {}", file.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustyline::history::History;
+
+    fn complete(line: &str) -> (usize, Vec<String>) {
+        let helper = LambdaHelper {
+            names: vec!["fac".to_string(), "fix".to_string(), "id".to_string()],
+            files: FilenameCompleter::new(),
+        };
+        let history = History::new();
+        let (start, pairs) = helper.complete(line, line.len(), &Context::new(&history)).unwrap();
+        (start, pairs.into_iter().map(|p| p.replacement).collect())
+    }
+
+    #[test]
+    fn completes_names() {
+        assert_eq!(complete("f"), (0, vec!["fac".to_string(), "fix".to_string()]));
+        assert_eq!(complete("(\\x. fa"), (5, vec!["fac".to_string()]));
+        assert_eq!(complete("λx.i"), (4, vec!["id".to_string()]));
+    }
+
+    #[test]
+    fn completes_commands() {
+        assert_eq!(complete(":st"), (1, vec!["step".to_string(), "strat".to_string()]));
+        // only the first word is a command
+        assert_eq!(complete(":eq f"), (4, vec!["fac".to_string(), "fix".to_string()]));
+    }
+}
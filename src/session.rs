@@ -0,0 +1,811 @@
+use crate::code::Exp;
+use crate::debruijn::DExp;
+use crate::parser::{ Def, Module, FileErrors, ParseError, parse, parse_spanned, parse_file, load_file, inline };
+use crate::json;
+use crate::dot;
+use crate::types::{ Type, infer };
+use crate::numeral::{ Encoding, desugar };
+use crate::decode::{ Decode, decode };
+use crate::prelude::PRELUDE;
+use crate::span::{ Span, SpanTree, Sources };
+use crate::reduce::{ Term, Strategy, Limits, NameStyle, Budget, Exhausted, Reduc, reduce_iter, reduce_full, reduce_step, free_in, sub,
+    strat_norm, strat_byname, strat_applic, strat_value, strat_norm_eta, strat_applic_eta, strat_hnf, strat_whnf,
+    is_normal, is_hnf, is_whnf };
+
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+/// Looks up a reduction strategy by its command line name.
+pub fn pick_strat<T: Term>(name: &str, eta: bool) -> Option<Strategy<T>> {
+    Some(match (name, eta) {
+        ("byname", _) => strat_byname,
+        ("normal", false) => strat_norm,
+        ("normal", true) => strat_norm_eta,
+        ("applicative", false) => strat_applic,
+        ("applicative", true) => strat_applic_eta,
+        ("value", _) => strat_value,
+        ("hnf", _) => strat_hnf,
+        ("whnf", _) => strat_whnf,
+        _ => return None
+    })
+}
+
+/// The commands understood by `Session::command`.
+pub const COMMANDS: &[&str] = &["defs", "doc", "eq", "list", "load", "quit", "step", "strat", "type", "undef"];
+
+/// When definitions are substituted into the terms that use them.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Expand {
+    /// Substituted before reduction starts, using the definitions as they
+    /// were when each name was defined.
+    Eager,
+    /// Substituted only once the term is otherwise irreducible, using the
+    /// latest definitions. Allows definitions to refer to themselves.
+    Lazy,
+}
+
+/// How terms and reduction steps are printed when running input.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    Text,
+    /// One JSON object per line, as encoded by the `json` module.
+    Json,
+}
+
+/// Graphviz output to draw instead of evaluating.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Dot {
+    /// The syntax tree of the term, with the next redex highlighted.
+    Tree,
+    /// The graph of reductions from the term, with at most this many nodes.
+    Graph(usize),
+}
+
+/// Settings controlling how input is evaluated.
+pub struct Config {
+    pub strat: String,
+    pub eta: bool,
+    pub nameless: bool,
+    pub verbose: bool,
+    /// The most steps a listing shows, or `None` to show them all. The rest
+    /// are counted, and the listing still ends with the last term.
+    pub max_listed: Option<usize>,
+    pub limits: Limits,
+    /// How bound variables are renamed to avoid capture.
+    pub names: NameStyle,
+    pub expand: Expand,
+    /// How numeric literals are encoded.
+    pub numerals: Encoding,
+    /// Which kinds of data results are shown as, rather than as lambda terms.
+    pub decode: Decode,
+    /// Only print results, not the terms being reduced or what was defined.
+    pub quiet: bool,
+    /// Print how long parsing and reduction take, to stderr.
+    pub timing: bool,
+    pub format: Format,
+    /// How input is read, or `None` to read files ending in `.json` as JSON
+    /// and everything else as lambda syntax.
+    pub input: Option<Format>,
+    pub dot: Option<Dot>,
+}
+
+/// How running some input went.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Status {
+    Success,
+    /// The input had parse errors, so nothing was run.
+    ParseError,
+    /// Reduction stopped before reaching a normal form, at a limit or when
+    /// interrupted.
+    GaveUp,
+}
+
+/// Evaluation state that persists between inputs: the settings, and the
+/// definitions made so far.
+pub struct Session {
+    pub conf: Config,
+    defs: Vec<Def>,
+    /// The text of all input so far, which spans point into.
+    sources: Sources,
+}
+
+impl Session {
+    pub fn new(conf: Config) -> Session {
+        Session { conf, defs: Vec::new(), sources: Sources::default() }
+    }
+
+    /// Adds a definition, replacing any earlier one of the same name.
+    pub fn define(&mut self, d: Def) {
+        let Def { name, val, spans, doc } = d;
+        let (val, spans) = desugar_spanned(val, spans, self.conf.numerals);
+        let (val, spans) = match self.conf.expand {
+            Expand::Eager => inline_spanned(val, spans, &self.defs, self.conf.names),
+            Expand::Lazy => (val, spans),
+        };
+        self.defs.retain(|d| d.name != name);
+        self.defs.push(Def { name, val, spans, doc });
+    }
+    /// Removes a definition, returning whether it existed.
+    pub fn undefine(&mut self, name: &str) -> bool {
+        let len = self.defs.len();
+        self.defs.retain(|d| d.name != name);
+        self.defs.len() != len
+    }
+
+    /// Adds the definitions of a module, and returns their names.
+    fn define_module(&mut self, defs: Vec<Def>) -> Vec<String> {
+        let names: Vec<String> = defs.iter().map(|d| d.name.clone()).collect();
+        for d in defs {
+            self.define(d);
+        }
+        names
+    }
+
+    /// Adds the definitions of the built-in prelude. Its arithmetic works on
+    /// Church numerals, so its own literals are always Church numerals,
+    /// whatever `numerals` says.
+    pub fn load_prelude(&mut self) {
+        let mut m = parse_file(PRELUDE).expect("the prelude doesn't parse");
+        m.shift(self.sources.add(Some("<prelude>"), PRELUDE));
+        let defs = m.defs.into_iter()
+            .map(|d| {
+                let (val, spans) = desugar_spanned(d.val, d.spans, Encoding::Church);
+                Def { val, spans, ..d }
+            })
+            .collect();
+        self.define_module(defs);
+    }
+
+    /// Handles a line of input, either a command or a program. Returns
+    /// `false` once the user asks to quit.
+    pub fn line(&mut self, inp: &str) -> bool {
+        match inp.trim_start().strip_prefix(':') {
+            Some(cmd) => self.command(cmd),
+            None => {
+                self.run(&[(inp, None)]);
+                true
+            }
+        }
+    }
+
+    /// Parses definitions and an optional main expression from each source,
+    /// given as its text and the file it came from, if any, which imports are
+    /// relative to. The definitions are added in order, and the last main
+    /// expression is evaluated. Nothing is defined if any source has errors.
+    pub fn run(&mut self, sources: &[(&str, Option<&str>)]) -> Status {
+        let now = Instant::now();
+        let mut modules = Vec::new();
+        let mut errors = Vec::new();
+        for &(inp, file) in sources {
+            let is_json = match self.conf.input {
+                Some(format) => format == Format::Json,
+                None => file.is_some_and(|f| f.ends_with(".json")),
+            };
+            let loaded = if is_json {
+                json::parse_term(inp)
+                    .map(|ex| Module { main: Some(ex), ..Module::default() })
+                    .map_err(|e| vec![FileErrors { file: file.map(String::from), src: inp.to_string(), errors: vec![e] }])
+            } else {
+                load_file(inp, file.map(Path::new), &mut self.sources)
+            };
+            match loaded {
+                Ok(m) => modules.push(m),
+                Err(e) => errors.extend(e),
+            }
+        }
+        if self.conf.timing {
+            eprintln!("Parse time: {:.3}ms", now.elapsed().as_millis() as f64 * 1e-3);
+        }
+
+        if errors.is_empty() {
+            let mut names = Vec::new();
+            let mut main = None;
+            for m in modules {
+                names.extend(self.define_module(m.defs));
+                if let Some(ex) = m.main {
+                    main = Some((ex, m.main_spans));
+                }
+            }
+            match main {
+                Some((ex, spans)) => {
+                    // spans are only needed to say where each step of a listing is
+                    let spans = spans.filter(|_| self.conf.verbose && self.conf.dot.is_none());
+                    let (ex, spans) = self.prepare(ex, spans);
+                    if let Some(how) = self.conf.dot {
+                        match how {
+                            Dot::Tree => {
+                                print!("{}", dot::tree(&ex, &self.strat()(&ex)));
+                            }
+                            Dot::Graph(max_nodes) => print!("{}", dot::graph(ex, self.conf.eta, self.conf.limits, self.conf.names, max_nodes)),
+                        }
+                        return Status::Success;
+                    }
+                    if !self.conf.quiet {
+                        match self.conf.format {
+                            Format::Text => println!("{}", ex),
+                            Format::Json => println!("{{\"input\":{}}}", json::term(&ex)),
+                        }
+                    }
+                    let now = Instant::now();
+                    let status = match self.normalize(ex, spans, self.conf.verbose) {
+                        Ok(ex) if self.conf.format == Format::Json => {
+                            let mut out = format!("{{\"result\":{}", json::term(&ex));
+                            if self.conf.decode != Decode::default() {
+                                out += ",\"value\":";
+                                json::quote(&self.show(&ex), &mut out);
+                            }
+                            println!("{}}}", out);
+                            Status::Success
+                        }
+                        Ok(ex) => {
+                            let shown = self.show(&ex);
+                            // the listing already ends with the result
+                            if !self.conf.verbose || shown != ex.to_string() {
+                                println!("{}", shown);
+                            }
+                            if !is_normal(&ex) && !self.conf.quiet {
+                                if is_hnf(&ex) {
+                                    println!("(head normal form)");
+                                } else if is_whnf(&ex) {
+                                    println!("(weak head normal form)");
+                                }
+                            }
+                            Status::Success
+                        }
+                        Err(e) if self.conf.format == Format::Json && !self.conf.quiet => {
+                            let budget = match e.budget {
+                                Budget::Steps => "steps",
+                                Budget::Size => "size",
+                                Budget::Interrupt => "interrupt",
+                            };
+                            println!("{{\"gave_up\":\"{}\",\"steps\":{},\"term\":{}}}", budget, e.steps, json::term(&e.ex));
+                            Status::GaveUp
+                        }
+                        Err(e) => {
+                            if self.conf.quiet {
+                                eprintln!("{}", gave_up(e.budget, e.steps, &self.conf.limits));
+                            } else {
+                                println!("{}", gave_up(e.budget, e.steps, &self.conf.limits));
+                                if !self.conf.verbose {
+                                    println!("{}", e.ex);
+                                }
+                            }
+                            Status::GaveUp
+                        }
+                    };
+                    if self.conf.timing {
+                        eprintln!("Eval time: {:.6}s", now.elapsed().as_micros() as f64 * 1e-6);
+                    }
+                    status
+                }
+                None => {
+                    if !names.is_empty() && !self.conf.quiet {
+                        match self.conf.format {
+                            Format::Text => println!("Defined {}", names.join(", ")),
+                            Format::Json => {
+                                let mut out = String::from("{\"defined\":[");
+                                for (i, name) in names.iter().enumerate() {
+                                    if i > 0 {
+                                        out.push(',');
+                                    }
+                                    json::quote(name, &mut out);
+                                }
+                                println!("{}]}}", out);
+                            }
+                        }
+                    }
+                    Status::Success
+                }
+            }
+        } else {
+            for e in errors {
+                eprint!("{}", e.report());
+            }
+            Status::ParseError
+        }
+    }
+
+    /// Names of the definitions made so far.
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.defs.iter().map(|d| &d.name)
+    }
+
+    /// Runs a REPL command, given without the leading `:`. Returns `false`
+    /// if the command was `:quit`.
+    pub fn command(&mut self, cmd: &str) -> bool {
+        let (name, arg) = match cmd.find(char::is_whitespace) {
+            Some(i) => (&cmd[..i], cmd[i..].trim()),
+            None => (cmd.trim(), "")
+        };
+        match name {
+            "eq" => match arg.find(" == ") {
+                Some(i) => match (parse(&arg[..i]), parse(&arg[i+4..])) {
+                    (Ok(a), Ok(b)) => match (self.normalize(self.prepare(a, None).0, None, false),
+                        self.normalize(self.prepare(b, None).0, None, false)) {
+                        (Ok(a), Ok(b)) => println!("{}", a.alpha_eq(&b)),
+                        (Err(e), _) | (_, Err(e)) => {
+                            println!("{}", gave_up(e.budget, e.steps, &self.conf.limits));
+                            println!("{}", e.ex);
+                        }
+                    }
+                    (Err(e), _) => eprint!("{}", e.report(&arg[..i], None)),
+                    (_, Err(e)) => eprint!("{}", e.report(&arg[i+4..], None)),
+                }
+                None => eprintln!("usage: :eq <term> == <term>")
+            }
+            "defs" => for d in &self.defs {
+                println!("{} := {}", d.name, d.val);
+            }
+            "undef" => if arg.is_empty() {
+                eprintln!("usage: :undef <name>");
+            } else if !self.undefine(arg) {
+                eprintln!("{} is not defined", arg);
+            }
+            "doc" => match self.defs.iter().find(|d| d.name == arg) {
+                Some(Def { doc: Some(doc), .. }) => println!("{}", doc),
+                Some(_) => println!("(no documentation)"),
+                None if arg.is_empty() => eprintln!("usage: :doc <name>"),
+                None => eprintln!("{} is not defined", arg),
+            }
+            "load" => if arg.is_empty() {
+                eprintln!("usage: :load <file>");
+            } else {
+                self.load(arg);
+            }
+            "type" => match self.type_of(arg) {
+                Ok(Some(t)) => println!("{}", t),
+                Ok(None) => println!("(no simple type)"),
+                Err(e) => eprint!("{}", e.report(arg, None)),
+            }
+            "step" => match parse_spanned(arg) {
+                Ok((ex, spans)) => {
+                    let spans = spans.shift(self.sources.add(None, arg));
+                    let (ex, spans) = self.prepare(ex, Some(spans));
+                    let (red, ex) = self.step(ex);
+                    if red == Reduc::Irred {
+                        println!("(irreducible)");
+                    } else {
+                        match spans.and_then(|spans| spans.at(&red)) {
+                            Some(span) => println!("=={}==> reducing `{}` at {}",
+                                red, self.sources.text(span), self.sources.show(span)),
+                            None => println!("=={}==>", red),
+                        }
+                        println!("{}", ex);
+                    }
+                }
+                Err(e) => eprint!("{}", e.report(arg, None)),
+            }
+            "strat" => if arg.is_empty() {
+                println!("{}", self.conf.strat);
+            } else if pick_strat::<Exp>(arg, self.conf.eta).is_some() {
+                self.conf.strat = arg.to_string();
+            } else {
+                eprintln!("Unknown strategy {}, expected one of byname, normal, applicative, value, hnf, whnf", arg);
+            }
+            "list" => match arg {
+                "" => println!("{}", if self.conf.verbose { "on" } else { "off" }),
+                "on" => self.conf.verbose = true,
+                "off" => self.conf.verbose = false,
+                _ => eprintln!("usage: :list on|off"),
+            }
+            "quit" | "q" => return false,
+            _ => eprintln!("Unknown command :{}", name)
+        }
+        true
+    }
+
+    /// Adds the definitions in a file. Its main expression, if any, is ignored.
+    fn load(&mut self, file: &str) {
+        let inp = match fs::read_to_string(file) {
+            Ok(inp) => inp,
+            Err(e) => return eprintln!("Couldn't read {}: {}", file, e),
+        };
+        match load_file(&inp, Some(Path::new(file)), &mut self.sources) {
+            Ok(m) => {
+                let names = self.define_module(m.defs);
+                println!("Loaded {}", names.join(", "));
+            }
+            Err(errors) => for e in errors {
+                eprint!("{}", e.report());
+            }
+        }
+    }
+
+    /// The simple type of a term, with definitions substituted in, if it has one.
+    fn type_of(&self, src: &str) -> Result<Option<Type>, ParseError> {
+        let ex = inline(desugar(parse(src)?, self.conf.numerals), &self.defs, self.conf.names);
+        Ok(infer(&ex))
+    }
+
+    /// The configured strategy, for either kind of term.
+    fn strat<T: Term>(&self) -> Strategy<T> {
+        pick_strat(&self.conf.strat, self.conf.eta).expect("invalid strategy")
+    }
+
+    /// Performs a single reduction step with the configured strategy.
+    fn step(&self, ex: Exp) -> (Reduc, Exp) {
+        if self.conf.nameless {
+            let (red, ex) = reduce_step(self.strat(), DExp::from_exp(&ex), ());
+            (red, ex.to_exp(self.conf.names))
+        } else {
+            reduce_step(self.strat(), ex, self.conf.names)
+        }
+    }
+
+    /// Turns numeric literals in a term about to be reduced into numerals, and
+    /// substitutes definitions into it if they are expanded eagerly. Where
+    /// the term came from is kept track of if `spans` says.
+    fn prepare(&self, ex: Exp, spans: Option<SpanTree>) -> (Exp, Option<SpanTree>) {
+        match spans {
+            Some(spans) => {
+                let (ex, spans) = desugar_spanned(ex, spans, self.conf.numerals);
+                let (ex, spans) = match self.conf.expand {
+                    Expand::Eager => inline_spanned(ex, spans, &self.defs, self.conf.names),
+                    Expand::Lazy => (ex, spans),
+                };
+                (ex, Some(spans))
+            }
+            None => {
+                let ex = desugar(ex, self.conf.numerals);
+                match self.conf.expand {
+                    Expand::Eager => (inline(ex, &self.defs, self.conf.names), None),
+                    Expand::Lazy => (ex, None),
+                }
+            }
+        }
+    }
+
+    /// Shows a result, decoding the kinds of data asked for.
+    fn show(&self, ex: &Exp) -> String {
+        decode(ex, self.conf.decode, self.conf.numerals).to_string()
+    }
+
+    /// Replaces every defined name that occurs free in `ex` with its
+    /// definition, or returns `None` if there are none.
+    fn expand_once(&self, ex: &Exp) -> Option<Exp> {
+        let used: Vec<&Def> = self.defs.iter().filter(|d| free_in(&d.name, ex)).collect();
+        if used.is_empty() {
+            None
+        } else {
+            Some(used.into_iter().fold(ex.clone(), |ex, d| sub(ex, &d.name, &d.val, self.conf.names)))
+        }
+    }
+
+    /// Reduces a term with the configured strategy, expanding definitions
+    /// whenever it gets stuck if they are expanded lazily. Expansions count
+    /// as a step towards the step limit, and are listed as `δ`. If `spans`
+    /// says where the term came from, the listing says where each redex is.
+    pub fn normalize(&self, ex: Exp, spans: Option<SpanTree>, verbose: bool) -> Result<Exp, Exhausted> {
+        let mut listed = 0;
+        let result = self.normalize_listed(ex, spans, verbose, &mut listed);
+        let more = self.conf.max_listed.map_or(0, |max| listed.saturating_sub(max));
+        if more > 0 {
+            let last = match &result {
+                Ok(ex) => ex,
+                Err(e) => &e.ex,
+            };
+            match self.conf.format {
+                Format::Text => println!("… {} more steps\n{}", more, last),
+                Format::Json => println!("{{\"more_steps\":{},\"term\":{}}}", more, json::term(last)),
+            }
+        }
+        result
+    }
+    /// Does the work of `normalize`, counting the steps listed in `listed`.
+    fn normalize_listed(&self, ex: Exp, spans: Option<SpanTree>, verbose: bool, listed: &mut usize)
+        -> Result<Exp, Exhausted> {
+        let mut ex = ex;
+        let mut spans = spans;
+        let mut limits = self.conf.limits;
+        let mut total = 0;
+        loop {
+            let (new, steps) = self.reduce(ex, &mut spans, limits, verbose, listed)
+                .map_err(|e| Exhausted { steps: total + e.steps, ..e })?;
+            total += steps;
+            limits.steps = limits.steps.map(|max| max - steps);
+            if self.conf.expand == Expand::Eager {
+                return Ok(new);
+            }
+            match self.expand_once(&new) {
+                None => return Ok(new),
+                Some(_) if limits.steps == Some(0) => {
+                    return Err(Exhausted { budget: Budget::Steps, steps: total, ex: new });
+                }
+                Some(next) => {
+                    spans = spans.map(|spans| spans.follow(&new, &next,
+                        &|n| self.defs.iter().find(|d| d.name == n).map(|d| &d.spans)));
+                    if verbose && self.should_list(listed) {
+                        self.list_step(None, None, &next);
+                    }
+                    total += 1;
+                    limits.steps = limits.steps.map(|max| max - 1);
+                    ex = next;
+                }
+            }
+        }
+    }
+
+    /// Reduces a term, listing the steps if `verbose` and counting them in
+    /// `listed`. `spans` is kept up to date with where each part of the term
+    /// came from.
+    fn reduce(&self, ex: Exp, spans: &mut Option<SpanTree>, limits: Limits, verbose: bool, listed: &mut usize)
+        -> Result<(Exp, usize), Exhausted> {
+        let mut list = |red: &Reduc, before: &Exp, after: &Exp| {
+            if !self.should_list(listed) {
+                // nothing more will say where a redex is
+                *spans = None;
+                return;
+            }
+            let at = spans.as_ref().and_then(|spans| spans.at(red));
+            *spans = spans.take().map(|spans| spans.reduce_with(before, red, after));
+            self.list_step(Some(red), at, after);
+        };
+        if self.conf.nameless {
+            let mut list = |red: &Reduc, before: &DExp, after: &DExp|
+                list(red, &before.to_exp(self.conf.names), &after.to_exp(self.conf.names));
+            reduce_listing(DExp::from_exp(&ex), self.strat(), limits, (), if verbose { Some(&mut list) } else { None })
+                .map(|(ex, steps)| (ex.to_exp(self.conf.names), steps))
+                .map_err(|e| Exhausted { budget: e.budget, steps: e.steps, ex: e.ex.to_exp(self.conf.names) })
+        } else {
+            reduce_listing(ex, self.strat(), limits, self.conf.names, if verbose { Some(&mut list) } else { None })
+        }
+    }
+
+    /// Counts a step of a listing, and says whether it is one to show.
+    fn should_list(&self, listed: &mut usize) -> bool {
+        *listed += 1;
+        self.conf.max_listed.is_none_or(|max| *listed <= max)
+    }
+
+    /// Prints a step of a reduction listing, with where the redex came from
+    /// if known. `None` stands for the expansion of definitions.
+    fn list_step(&self, red: Option<&Reduc>, at: Option<Span>, ex: &Exp) {
+        let at = at.map(|span| self.sources.show(span));
+        match (self.conf.format, red, at) {
+            (Format::Text, Some(red), Some(at)) => println!("=={}==> at {}\n{}", red, at, ex),
+            (Format::Text, Some(red), None) => println!("=={}==>\n{}", red, ex),
+            (Format::Text, None, _) => println!("==δ==>\n{}", ex),
+            (Format::Json, red, at) => println!("{}", json::step(red, at.as_deref(), ex)),
+        }
+    }
+}
+
+/// Like `desugar`, keeping track of where the term came from. A numeral
+/// spans the literal it was written as.
+fn desugar_spanned(ex: Exp, spans: SpanTree, enc: Encoding) -> (Exp, SpanTree) {
+    let new = desugar(ex.clone(), enc);
+    let spans = spans.follow(&ex, &new, &|_| None);
+    (new, spans)
+}
+
+/// Like `inline`, keeping track of where the term came from: the parts of a
+/// definition substituted in point to where it was defined.
+fn inline_spanned(ex: Exp, spans: SpanTree, defs: &[Def], names: NameStyle) -> (Exp, SpanTree) {
+    let new = inline(ex.clone(), defs, names);
+    let spans = spans.follow(&ex, &new, &|n| defs.iter().rev().find(|d| d.name == n).map(|d| &d.spans));
+    (new, spans)
+}
+
+/// Something to show each step of a reduction with, given the term before
+/// and after it.
+type Listing<'a, T> = &'a mut dyn FnMut(&Reduc, &T, &T);
+
+/// Like `reduce_full`, but passes each step to `list` if given.
+fn reduce_listing<T: Term>(ex: T, strat: Strategy<T>, limits: Limits, names: T::Names, list: Option<Listing<T>>)
+    -> Result<(T, usize), Exhausted<T>> {
+    let list = match list {
+        Some(list) => list,
+        None => return reduce_full(strat, ex, limits, names),
+    };
+    let mut iter = reduce_iter(strat, ex.clone()).limit(limits).names(names);
+    let mut last = ex;
+    for (red, ex) in iter.by_ref() {
+        list(&red, &last, &ex);
+        last = ex;
+    }
+    match iter.exhausted() {
+        Some(budget) => Err(Exhausted { budget, steps: iter.steps(), ex: last }),
+        None => Ok((last, iter.steps())),
+    }
+}
+
+fn gave_up(budget: Budget, steps: usize, limits: &Limits) -> String {
+    match budget {
+        Budget::Steps => format!("Gave up after {} steps", steps),
+        Budget::Size => format!("Gave up after {} steps: term grew beyond {} nodes",
+            steps, limits.size.unwrap_or(0)),
+        Budget::Interrupt => format!("Interrupted after {} steps", steps),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::numeral::encode;
+
+    fn session(expand: Expand) -> Session {
+        Session::new(Config {
+            strat: "normal".to_string(),
+            eta: false,
+            nameless: false,
+            verbose: false,
+            max_listed: None,
+            limits: Limits { steps: Some(100), ..Limits::default() },
+            names: NameStyle::Prime,
+            expand,
+            numerals: Encoding::Church,
+            decode: Decode::default(),
+            quiet: false,
+            timing: false,
+            format: Format::Text,
+            input: None,
+            dot: None,
+        })
+    }
+
+    fn define(s: &mut Session, name: &str, src: &str) -> Result<(), ParseError> {
+        let (val, spans) = parse_spanned(src)?;
+        let spans = spans.shift(s.sources.add(None, src));
+        s.define(Def { name: name.to_string(), val, spans, doc: None });
+        Ok(())
+    }
+
+    #[test]
+    fn eager_definitions() -> Result<(), ParseError> {
+        let mut s = session(Expand::Eager);
+        define(&mut s, "I", "\\x. x")?;
+        define(&mut s, "II", "I I")?;
+        // II keeps the I it was defined with
+        define(&mut s, "I", "\\x y. x")?;
+        assert_eq!(s.normalize(s.prepare(parse("II z")?, None).0, None, false).ok(), parse("z").ok());
+        assert_eq!(s.normalize(s.prepare(parse("I z")?, None).0, None, false).ok(), parse("\\y. z").ok());
+        Ok(())
+    }
+
+    #[test]
+    fn lazy_definitions() -> Result<(), ParseError> {
+        let mut s = session(Expand::Lazy);
+        define(&mut s, "II", "I I")?;
+        define(&mut s, "I", "\\x. x")?;
+        assert_eq!(s.normalize(s.prepare(parse("II z")?, None).0, None, false).ok(), parse("z").ok());
+        // definitions may refer to themselves, and expansions count as steps
+        define(&mut s, "loop", "loop")?;
+        let e = s.normalize(parse("loop")?, None, false).unwrap_err();
+        assert_eq!((e.budget, e.steps), (Budget::Steps, 100));
+        Ok(())
+    }
+
+    #[test]
+    fn undefine() -> Result<(), ParseError> {
+        let mut s = session(Expand::Eager);
+        define(&mut s, "I", "\\x. x")?;
+        assert!(s.undefine("I"));
+        assert!(!s.undefine("I"));
+        assert_eq!(s.normalize(s.prepare(parse("I z")?, None).0, None, false).ok(), parse("I z").ok());
+        Ok(())
+    }
+
+    #[test]
+    fn numerals() -> Result<(), ParseError> {
+        let mut s = session(Expand::Eager);
+        define(&mut s, "*", "\\m n f. m (n f)")?;
+        let ex = s.normalize(s.prepare(parse("* 2 3")?, None).0, None, false).unwrap();
+        assert_eq!(ex, encode(6, Encoding::Church));
+        assert_eq!(s.show(&ex), "\\f x. f (f (f (f (f (f x)))))");
+        s.conf.decode = Decode::all();
+        assert_eq!(s.show(&ex), "6");
+        Ok(())
+    }
+
+    #[test]
+    fn prelude_numerals() -> Result<(), ParseError> {
+        let mut s = session(Expand::Eager);
+        s.conf.numerals = Encoding::Scott;
+        s.conf.limits.steps = Some(10_000);
+        s.load_prelude();
+        let ex = s.normalize(s.prepare(parse("length (cons a (cons b nil))")?, None).0, None, false).unwrap();
+        assert_eq!(ex, encode(2, Encoding::Church));
+        let ex = s.normalize(s.prepare(parse("+ (succ (succ (\\f x. x))) (succ (\\f x. x))")?, None).0, None, false).unwrap();
+        assert_eq!(ex, encode(3, Encoding::Church));
+        Ok(())
+    }
+
+    #[test]
+    fn spans() -> Result<(), ParseError> {
+        let mut s = session(Expand::Eager);
+        assert_eq!(s.run(&[("K := \\x y. x\nI := \\x. x", Some("defs.lb"))]), Status::Success);
+        let src = "K (I z) 2";
+        let (ex, spans) = parse_spanned(src)?;
+        let spans = spans.shift(s.sources.add(None, src));
+        let (ex, spans) = s.prepare(ex, Some(spans));
+        let spans = spans.unwrap();
+        assert!(spans.fits(&ex));
+        let show = |spans: &SpanTree, red: &Reduc| s.sources.show(spans.at(red).unwrap());
+        let (red, next) = s.step(ex.clone());
+        assert_eq!(show(&spans, &red), "1:1-1:8");
+        // K's body comes from where K is defined, and the argument
+        // substituted into it from where the argument is written
+        let spans = spans.reduce_with(&ex, &red, &next);
+        assert!(spans.fits(&next));
+        let body = Reduc::Left(Box::new(Reduc::Body(Box::new(Reduc::Beta))));
+        assert_eq!(show(&spans, &body), "1:3-1:8");
+        assert_eq!(s.sources.text(spans.at(&body).unwrap()), "(I z)");
+        match &spans {
+            SpanTree::Call(_, k, two) => {
+                assert_eq!(s.sources.show(k.span()), "defs.lb:1:9-1:13");
+                assert_eq!(s.sources.text(two.span()), "2");
+            }
+            _ => panic!("{:?} is not a call", spans),
+        }
+        Ok(())
+    }
+    #[test]
+    fn substituted_spans() -> Result<(), ParseError> {
+        let s = session(Expand::Eager);
+        let (mut ex, mut spans) = parse_spanned("(\\x. x x) (\\x. x x)")?;
+        let mut at = Vec::new();
+        for _ in 0..3 {
+            let (red, next) = s.step(ex.clone());
+            at.push(spans.at(&red).unwrap());
+            spans = spans.reduce_with(&ex, &red, &next);
+            ex = next;
+        }
+        // only the first redex is written in the source; the others are
+        // applications of the argument, which was substituted in
+        assert_eq!(at, vec![Span::new(0, 19), Span::new(10, 19), Span::new(10, 19)]);
+        Ok(())
+    }
+
+    #[test]
+    fn long_listings() -> Result<(), ParseError> {
+        let mut s = session(Expand::Lazy);
+        s.conf.max_listed = Some(3);
+        define(&mut s, "loop", "loop")?;
+        let e = s.normalize(parse("(\\x. x) loop")?, None, true).unwrap_err();
+        assert_eq!((e.budget, e.steps), (Budget::Steps, 100));
+        let mut listed = 0;
+        let shown = (0..5).filter(|_| s.should_list(&mut listed)).count();
+        assert_eq!((shown, listed), (3, 5));
+        Ok(())
+    }
+
+    #[test]
+    fn docs() {
+        let mut s = session(Expand::Eager);
+        assert_eq!(s.run(&[("## A.\na := x\n## B.\nb := y", None)]), Status::Success);
+        // redefining a name replaces its doc comment too
+        assert_eq!(s.run(&[("b := z", None)]), Status::Success);
+        let docs: Vec<(&str, Option<&str>)> = s.defs.iter().map(|d| (d.name.as_str(), d.doc.as_deref())).collect();
+        assert_eq!(docs, vec![("a", Some("A.")), ("b", None)]);
+    }
+
+    #[test]
+    fn sources() {
+        let mut s = session(Expand::Eager);
+        assert_eq!(s.run(&[("a := x\nb := y\na", None), ("b := a a", None)]), Status::Success);
+        assert_eq!(s.names().collect::<Vec<_>>(), vec!["a", "b"]);
+        // nothing is defined if any source has errors
+        assert_eq!(s.run(&[("c := z", None), ("d := (", None)]), Status::ParseError);
+        assert_eq!(s.names().count(), 2);
+        assert_eq!(s.run(&[("(\\x. x x) (\\x. x x)", None)]), Status::GaveUp);
+        // `{` can start a name, so only the extension or the setting says
+        // that input is JSON
+        assert_eq!(s.run(&[("{a} b", Some("term.lb")), ("{\"var\":\"x\"}", None)]), Status::Success);
+        assert_eq!(s.run(&[("{\"var\":\"x\"", Some("term.json"))]), Status::ParseError);
+        s.conf.input = Some(Format::Json);
+        assert_eq!(s.run(&[(" {\"app\":[{\"var\":\"b\"},{\"var\":\"z\"}]}", None)]), Status::Success);
+        assert_eq!(s.run(&[("{a} b", Some("term.lb"))]), Status::ParseError);
+    }
+
+    #[test]
+    fn types() -> Result<(), ParseError> {
+        let mut s = session(Expand::Eager);
+        assert_eq!(s.run(&[("K := \\x y. x", None)]), Status::Success);
+        assert!(s.command("type K"));
+        assert_eq!(s.type_of("K")?.map(|t| t.to_string()), Some("a -> b -> a".to_string()));
+        assert!(s.command("type (\\x. x x) (\\x. x x)"));
+        assert_eq!(s.type_of("(\\x. x x) (\\x. x x)")?, None);
+        Ok(())
+    }
+}
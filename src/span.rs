@@ -0,0 +1,301 @@
+use crate::code::Exp;
+use crate::reduce::Reduc;
+
+use std::fmt;
+
+/// A range of byte offsets into the source text, `start` inclusive and `end`
+/// exclusive.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize
+}
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+    /// Shows the span as `line:col-line:col` in `src`.
+    pub fn show<'a>(&self, src: &'a str) -> ShowSpan<'a> {
+        ShowSpan { span: *self, src }
+    }
+}
+
+/// The 1-based line and column of byte offset `pos` in `src`, counting
+/// columns in characters.
+pub fn line_col(pos: usize, src: &str) -> (usize, usize) {
+    let before = &src.as_bytes()[..pos.min(src.len())];
+    let line_start = before.iter().rposition(|&c| c == b'\n').map_or(0, |i| i + 1);
+    let line = 1 + before.iter().filter(|&&c| c == b'\n').count();
+    let col = 1 + String::from_utf8_lossy(&before[line_start..]).chars().count();
+    (line, col)
+}
+
+/// The texts that spans point into, laid end to end, so that a span also
+/// says which of them it is in. Spans from parsing a text are shifted by
+/// where it starts.
+#[derive(Debug, Default)]
+pub struct Sources {
+    /// Each text with the file it came from, if any, and where it starts.
+    texts: Vec<(Option<String>, String, usize)>,
+    len: usize
+}
+impl Sources {
+    /// Adds a text, returning how far to shift spans into it.
+    pub fn add(&mut self, file: Option<&str>, text: &str) -> usize {
+        let start = self.len;
+        // the gap keeps the end of one text apart from the start of the next
+        self.len += text.len() + 1;
+        self.texts.push((file.map(String::from), text.to_string(), start));
+        start
+    }
+    /// The text a span is in, and the span within it.
+    fn find(&self, span: Span) -> Option<(&Option<String>, &str, Span)> {
+        self.texts.iter().rev().find(|t| t.2 <= span.start)
+            .map(|(file, text, start)| (file, text.as_str(), Span::new(span.start - start, span.end - start)))
+    }
+    /// The source text a span covers.
+    pub fn text(&self, span: Span) -> &str {
+        self.find(span).map_or("", |(_, text, span)| &text[span.start..span.end.min(text.len())])
+    }
+    /// Shows a span as `file:line:col-line:col`, leaving out the file for
+    /// text that didn't come from one.
+    pub fn show(&self, span: Span) -> String {
+        match self.find(span) {
+            Some((Some(file), text, span)) => format!("{}:{}", file, span.show(text)),
+            Some((None, text, span)) => span.show(text).to_string(),
+            None => format!("{}-{}", span.start, span.end),
+        }
+    }
+}
+
+pub struct ShowSpan<'a> {
+    span: Span,
+    src: &'a str
+}
+impl fmt::Display for ShowSpan<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (l1, c1) = line_col(self.span.start, self.src);
+        let (l2, c2) = line_col(self.span.end, self.src);
+        write!(f, "{}:{}-{}:{}", l1, c1, l2, c2)
+    }
+}
+
+/// Where each node of a parsed `Exp` came from. Has the same shape as the
+/// expression it was parsed alongside, so paths into one are paths into the
+/// other. Nodes that come from desugaring span the construct they were made
+/// from.
+#[derive(Debug, PartialEq, Clone)]
+pub enum SpanTree {
+    Var(Span),
+    Call(Span, Box<SpanTree>, Box<SpanTree>),
+    Lamb(Span, Box<SpanTree>),
+    /// A `let`, which stands for a lambda applied to the value, with the
+    /// trees of its body and value, and its doc comment if it has one.
+    Let(Span, Box<SpanTree>, Box<SpanTree>, Option<String>),
+    /// A part of the source that failed to parse. The expression has a
+    /// variable in its place.
+    Error(Span),
+}
+impl SpanTree {
+    pub fn span(&self) -> Span {
+        match self {
+            SpanTree::Var(s) | SpanTree::Call(s, _, _) | SpanTree::Lamb(s, _) | SpanTree::Let(s, _, _, _) |
+                SpanTree::Error(s) => *s
+        }
+    }
+    /// A tree of the same shape as `ex` with every node spanning `span`, for
+    /// terms that stand in for a single piece of the source.
+    pub fn uniform(ex: &Exp, span: Span) -> SpanTree {
+        match ex {
+            Exp::Var(_) => SpanTree::Var(span),
+            Exp::Call(a, b) => SpanTree::Call(span, Box::new(SpanTree::uniform(a, span)), Box::new(SpanTree::uniform(b, span))),
+            Exp::Lamb(_, r) => SpanTree::Lamb(span, Box::new(SpanTree::uniform(r, span))),
+        }
+    }
+    /// The same tree with every span moved `by` bytes along.
+    pub fn shift(self, by: usize) -> SpanTree {
+        let move_by = |s: Span| Span::new(s.start + by, s.end + by);
+        match self {
+            SpanTree::Var(s) => SpanTree::Var(move_by(s)),
+            SpanTree::Call(s, a, b) => SpanTree::Call(move_by(s), Box::new(a.shift(by)), Box::new(b.shift(by))),
+            SpanTree::Lamb(s, r) => SpanTree::Lamb(move_by(s), Box::new(r.shift(by))),
+            SpanTree::Let(s, body, val, doc) => SpanTree::Let(move_by(s), Box::new(body.shift(by)), Box::new(val.shift(by)), doc),
+            SpanTree::Error(s) => SpanTree::Error(move_by(s)),
+        }
+    }
+    /// The tree for `after`, which is `before`, the term this tree is for,
+    /// with some of its variables replaced by terms. A replaced variable
+    /// gets the tree `new` gives for its name if there is one that fits,
+    /// and otherwise its own span throughout.
+    pub fn follow<'a>(self, before: &Exp, after: &Exp, new: &dyn Fn(&str) -> Option<&'a SpanTree>) -> SpanTree {
+        match (self.unlet(), before, after) {
+            (sp, Exp::Var(x), after) => match new(x) {
+                Some(tree) if tree.fits(after) => tree.clone(),
+                _ if matches!(after, Exp::Var(_)) => sp,
+                _ => SpanTree::uniform(after, sp.span()),
+            }
+            (SpanTree::Call(s, a, b), Exp::Call(x, y), Exp::Call(p, q)) =>
+                SpanTree::Call(s, Box::new(a.follow(x, p, new)), Box::new(b.follow(y, q, new))),
+            (SpanTree::Lamb(s, r), Exp::Lamb(v, x), Exp::Lamb(_, p)) =>
+                // a variable bound here isn't one that was replaced
+                SpanTree::Lamb(s, Box::new(r.follow(x, p, &|n| if n == v { None } else { new(n) }))),
+            (sp, _, after) => SpanTree::uniform(after, sp.span()),
+        }
+    }
+    /// The tree for `after`, the result of contracting the redex `red`
+    /// points to in `ex`, the term this tree is for. Each node keeps the
+    /// span of the node it came from, so the parts of a substituted
+    /// argument point to where the argument was written. Applications of
+    /// a substituted variable aren't written anywhere once it is replaced,
+    /// so they span the argument instead.
+    pub fn reduce_with(self, ex: &Exp, red: &Reduc, after: &Exp) -> SpanTree {
+        match (self.unlet(), ex, red, after) {
+            (SpanTree::Call(s, a, b), Exp::Call(x, _), Reduc::Left(r), Exp::Call(p, _)) =>
+                SpanTree::Call(s, Box::new(a.reduce_with(x, r, p)), b),
+            (SpanTree::Call(s, a, b), Exp::Call(_, y), Reduc::Right(r), Exp::Call(_, q)) =>
+                SpanTree::Call(s, a, Box::new(b.reduce_with(y, r, q))),
+            (SpanTree::Lamb(s, r), Exp::Lamb(_, x), Reduc::Body(red), Exp::Lamb(_, p)) =>
+                SpanTree::Lamb(s, Box::new(r.reduce_with(x, red, p))),
+            (SpanTree::Call(s, f, arg), Exp::Call(g, _), Reduc::Beta, after) => match (*f, &**g) {
+                (SpanTree::Lamb(_, body), Exp::Lamb(x, r)) =>
+                    body.follow(r, after, &|n| if n == x { Some(&*arg) } else { None }).relocate(r, x),
+                _ => SpanTree::uniform(after, s),
+            }
+            (SpanTree::Lamb(s, body), Exp::Lamb(..), Reduc::Eta, after) => match *body {
+                SpanTree::Call(_, f, _) if f.fits(after) => *f,
+                _ => SpanTree::uniform(after, s),
+            }
+            (sp, _, _, after) => SpanTree::uniform(after, sp.span()),
+        }
+    }
+    /// The tree for the lambda body `body` once `x` is substituted in it,
+    /// with each application that `x` is at the head of spanning what was
+    /// substituted for it.
+    fn relocate(self, body: &Exp, x: &str) -> SpanTree {
+        match (self, body) {
+            (SpanTree::Call(s, a, b), Exp::Call(f, y)) => {
+                let a = a.relocate(f, x);
+                let s = if applies(f, x) { a.span() } else { s };
+                SpanTree::Call(s, Box::new(a), Box::new(b.relocate(y, x)))
+            }
+            (SpanTree::Lamb(s, r), Exp::Lamb(v, p)) if v != x => SpanTree::Lamb(s, Box::new(r.relocate(p, x))),
+            (sp, _) => sp,
+        }
+    }
+    /// Turns a `Let` at the root into the lambda applied to the value that
+    /// it stands for, both spanning the `let`.
+    fn unlet(self) -> SpanTree {
+        match self {
+            SpanTree::Let(s, body, val, _) => SpanTree::Call(s, Box::new(SpanTree::Lamb(s, body)), val),
+            sp => sp,
+        }
+    }
+    /// The same tree, with the span of the root replaced.
+    pub fn with_span(self, span: Span) -> SpanTree {
+        match self {
+            SpanTree::Var(_) => SpanTree::Var(span),
+            SpanTree::Call(_, a, b) => SpanTree::Call(span, a, b),
+            SpanTree::Lamb(_, r) => SpanTree::Lamb(span, r),
+            SpanTree::Let(_, body, val, doc) => SpanTree::Let(span, body, val, doc),
+            SpanTree::Error(_) => SpanTree::Error(span),
+        }
+    }
+    /// The span of the redex that a reduction path points to, or `None` if
+    /// the path doesn't fit this tree or is `Irred`.
+    pub fn at(&self, red: &Reduc) -> Option<Span> {
+        match (red, self) {
+            (Reduc::Left(r), SpanTree::Call(_, a, _)) => a.at(r),
+            (Reduc::Right(r), SpanTree::Call(_, _, b)) => b.at(r),
+            (Reduc::Body(r), SpanTree::Lamb(_, b)) => b.at(r),
+            (Reduc::Beta, SpanTree::Call(s, _, _)) | (Reduc::Eta, SpanTree::Lamb(s, _)) => Some(*s),
+            (Reduc::Left(r), SpanTree::Let(s, body, _, _)) => match &**r {
+                Reduc::Body(r) => body.at(r),
+                Reduc::Eta => Some(*s),
+                _ => None,
+            }
+            (Reduc::Right(r), SpanTree::Let(_, _, val, _)) => val.at(r),
+            (Reduc::Beta, SpanTree::Let(s, _, _, _)) => Some(*s),
+            _ => None,
+        }
+    }
+    /// Checks whether this tree has the same shape as `ex`.
+    pub fn fits(&self, ex: &Exp) -> bool {
+        match (self, ex) {
+            (SpanTree::Var(_), Exp::Var(_)) | (SpanTree::Error(_), Exp::Var(_)) => true,
+            (SpanTree::Call(_, a, b), Exp::Call(x, y)) => a.fits(x) && b.fits(y),
+            (SpanTree::Lamb(_, r), Exp::Lamb(_, s)) => r.fits(s),
+            (SpanTree::Let(_, body, val, _), Exp::Call(f, x)) =>
+                matches!(&**f, Exp::Lamb(_, r) if body.fits(r)) && val.fits(x),
+            _ => false,
+        }
+    }
+}
+
+/// Checks whether `f` is the variable `x`, or an application with it at the head.
+fn applies(f: &Exp, x: &str) -> bool {
+    match f {
+        Exp::Var(v) => v == x,
+        Exp::Call(g, _) => applies(g, x),
+        Exp::Lamb(..) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::numeral::{ Encoding, desugar };
+    use crate::parser::{ parse_spanned, ParseError };
+    use crate::reduce::{ NameStyle, reduce_step, strat_norm };
+
+    /// The source text of each variable in a tree, from left to right.
+    fn texts<'a>(spans: &SpanTree, src: &'a str) -> Vec<&'a str> {
+        match spans {
+            SpanTree::Call(_, a, b) => [texts(a, src), texts(b, src)].concat(),
+            SpanTree::Lamb(_, r) => texts(r, src),
+            SpanTree::Let(_, body, val, _) => [texts(body, src), texts(val, src)].concat(),
+            sp => vec![&src[sp.span().start..sp.span().end]],
+        }
+    }
+    /// Contracts the first redex of `src`, giving `texts` of the result.
+    fn step(src: &str) -> Result<Vec<&str>, ParseError> {
+        let (ex, spans) = parse_spanned(src)?;
+        let (red, next) = reduce_step(strat_norm, ex.clone(), NameStyle::Prime);
+        let spans = spans.reduce_with(&ex, &red, &next);
+        assert!(spans.fits(&next));
+        Ok(texts(&spans, src))
+    }
+
+    #[test]
+    fn reductions() -> Result<(), ParseError> {
+        // each copy of the argument points to where it was written
+        assert_eq!(step("(\\x. x x) (\\y. y)")?, vec!["y", "y"]);
+        assert_eq!(step("(\\x. f x (g x)) a")?, vec!["f", "a", "g", "a"]);
+        // a variable of the same name bound inside isn't replaced
+        assert_eq!(step("(\\x. \\x. x) y")?, vec!["x"]);
+        assert_eq!(step("(\\x. \\y. x y) y")?, vec!["y", "y"]);
+        // reducing inside keeps the rest
+        assert_eq!(step("f ((\\x. x) a) b")?, vec!["f", "a", "b"]);
+        // a numeral stands for the literal it was written as
+        let (ex, spans) = parse_spanned("f 2")?;
+        let new = desugar(ex.clone(), Encoding::Church);
+        let spans = spans.follow(&ex, &new, &|_| None);
+        assert!(spans.fits(&new));
+        assert_eq!(texts(&spans, "f 2"), vec!["f", "2", "2", "2"]);
+        Ok(())
+    }
+
+    #[test]
+    fn sources() {
+        let mut sources = Sources::default();
+        let a = sources.add(Some("a.lb"), "x\ny z");
+        let b = sources.add(None, "w");
+        assert_eq!(sources.show(Span::new(a + 4, a + 5)), "a.lb:2:3-2:4");
+        assert_eq!(sources.text(Span::new(a + 2, a + 5)), "y z");
+        assert_eq!(sources.show(Span::new(b, b + 1)), "1:1-1:2");
+        assert_eq!(sources.text(Span::new(b, b + 1)), "w");
+    }
+}
@@ -0,0 +1,174 @@
+use crate::code::Exp;
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// A simple type: either a type variable, or a function type.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Type {
+    Var(usize),
+    Fun(Box<Type>, Box<Type>),
+}
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Var(n) => {
+                write!(f, "{}", (b'a' + (n % 26) as u8) as char)?;
+                if *n >= 26 {
+                    write!(f, "{}", n / 26)?;
+                }
+                Ok(())
+            }
+            Type::Fun(a, b) => match **a {
+                Type::Fun(..) => write!(f, "({}) -> {}", a, b),
+                Type::Var(_) => write!(f, "{} -> {}", a, b),
+            }
+        }
+    }
+}
+
+/// Infers the most general simple type of an expression, or `None` if it
+/// has none (like `\x. x x`). Free variables may have any type, as long as
+/// it is the same at each use.
+pub fn infer(ex: &Exp) -> Option<Type> {
+    let mut inf = Infer { subst: Vec::new(), free: HashMap::new() };
+    let t = inf.infer(ex, &mut Vec::new())?;
+    let t = inf.resolve(&t);
+    Some(rename(&t, &mut Vec::new()))
+}
+
+struct Infer {
+    /// What each type variable has been unified with, if anything.
+    subst: Vec<Option<Type>>,
+    free: HashMap<String, Type>,
+}
+impl Infer {
+    fn fresh(&mut self) -> Type {
+        self.subst.push(None);
+        Type::Var(self.subst.len() - 1)
+    }
+
+    fn infer(&mut self, ex: &Exp, env: &mut Vec<(String, Type)>) -> Option<Type> {
+        match ex {
+            Exp::Var(x) => match env.iter().rev().find(|(y, _)| y == x) {
+                Some((_, t)) => Some(t.clone()),
+                None => {
+                    if !self.free.contains_key(x) {
+                        let t = self.fresh();
+                        self.free.insert(x.clone(), t);
+                    }
+                    Some(self.free[x].clone())
+                }
+            }
+            Exp::Lamb(x, r) => {
+                let arg = self.fresh();
+                env.push((x.clone(), arg.clone()));
+                let body = self.infer(r, env);
+                env.pop();
+                Some(Type::Fun(Box::new(arg), Box::new(body?)))
+            }
+            Exp::Call(a, b) => {
+                let fun = self.infer(a, env)?;
+                let arg = self.infer(b, env)?;
+                let res = self.fresh();
+                if self.unify(&fun, &Type::Fun(Box::new(arg), Box::new(res.clone()))) {
+                    Some(res)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Follows the substitution until reaching an unbound variable or a function type.
+    fn walk(&self, t: &Type) -> Type {
+        match t {
+            Type::Var(v) => match &self.subst[*v] {
+                Some(t) => self.walk(t),
+                None => t.clone(),
+            }
+            Type::Fun(..) => t.clone(),
+        }
+    }
+
+    /// Applies the substitution everywhere in a type.
+    fn resolve(&self, t: &Type) -> Type {
+        match self.walk(t) {
+            Type::Fun(a, b) => Type::Fun(Box::new(self.resolve(&a)), Box::new(self.resolve(&b))),
+            t => t,
+        }
+    }
+
+    fn occurs(&self, v: usize, t: &Type) -> bool {
+        match self.walk(t) {
+            Type::Var(w) => v == w,
+            Type::Fun(a, b) => self.occurs(v, &a) || self.occurs(v, &b),
+        }
+    }
+
+    fn unify(&mut self, a: &Type, b: &Type) -> bool {
+        match (self.walk(a), self.walk(b)) {
+            (Type::Var(v), Type::Var(w)) if v == w => true,
+            (Type::Var(v), t) | (t, Type::Var(v)) => {
+                if self.occurs(v, &t) {
+                    false
+                } else {
+                    self.subst[v] = Some(t);
+                    true
+                }
+            }
+            (Type::Fun(a1, b1), Type::Fun(a2, b2)) => self.unify(&a1, &a2) && self.unify(&b1, &b2),
+        }
+    }
+}
+
+/// Renumbers type variables in order of first appearance.
+fn rename(t: &Type, seen: &mut Vec<usize>) -> Type {
+    match t {
+        Type::Var(v) => match seen.iter().position(|w| w == v) {
+            Some(i) => Type::Var(i),
+            None => {
+                seen.push(*v);
+                Type::Var(seen.len() - 1)
+            }
+        }
+        Type::Fun(a, b) => {
+            let a = rename(a, seen);
+            Type::Fun(Box::new(a), Box::new(rename(b, seen)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{ parse, ParseError };
+
+    fn type_of(s: &str) -> Result<Option<String>, ParseError> {
+        Ok(infer(&parse(s)?).map(|t| t.to_string()))
+    }
+
+    #[test]
+    fn combinators() -> Result<(), ParseError> {
+        assert_eq!(type_of("\\x. x")?.as_deref(), Some("a -> a"));
+        assert_eq!(type_of("\\x y. x")?.as_deref(), Some("a -> b -> a"));
+        assert_eq!(type_of("\\x y z. x z (y z)")?.as_deref(), Some("(a -> b -> c) -> (a -> b) -> a -> c"));
+        assert_eq!(type_of("\\f x. f (f x)")?.as_deref(), Some("(a -> a) -> a -> a"));
+        Ok(())
+    }
+
+    #[test]
+    fn untypable() -> Result<(), ParseError> {
+        assert_eq!(type_of("\\x. x x")?, None);
+        assert_eq!(type_of("(\\x. x x) (\\x. x x)")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn free_variables() -> Result<(), ParseError> {
+        assert_eq!(type_of("f x")?.as_deref(), Some("a"));
+        assert_eq!(type_of("\\x. f x x")?.as_deref(), Some("a -> b"));
+        assert_eq!(type_of("\\x. f (f x)")?.as_deref(), Some("a -> a"));
+        Ok(())
+    }
+}